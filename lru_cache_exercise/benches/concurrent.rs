@@ -0,0 +1,104 @@
+//! Read-heavy multi-threaded throughput of the shared caches against a
+//! plain `Mutex<LruCache>`.
+//!
+//! Run with `cargo bench --bench concurrent`. Each thread looks keys up and
+//! inserts on a miss; the key space is sized for roughly 99% hits.
+
+use std::hint::black_box;
+use std::sync::{Arc, Barrier, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use lru_cache_exercise::{BufferedLruCache, ConcurrentLruCache, LruCache};
+
+const CAPACITY: u64 = 10_000;
+const KEY_SPACE: u64 = CAPACITY * 101 / 100;
+const OPS_PER_THREAD: u64 = 1_000_000;
+
+/// The operations every contender supports, behind `&self`.
+trait SharedCache: Send + Sync + 'static {
+    fn get(&self, key: u64) -> Option<u64>;
+    fn put(&self, key: u64, value: u64);
+}
+
+impl SharedCache for Mutex<LruCache<u64, u64>> {
+    fn get(&self, key: u64) -> Option<u64> {
+        self.lock().unwrap().get(&key).copied()
+    }
+
+    fn put(&self, key: u64, value: u64) {
+        self.lock().unwrap().put(key, value);
+    }
+}
+
+impl SharedCache for ConcurrentLruCache<u64, u64> {
+    fn get(&self, key: u64) -> Option<u64> {
+        ConcurrentLruCache::get(self, &key)
+    }
+
+    fn put(&self, key: u64, value: u64) {
+        ConcurrentLruCache::put(self, key, value);
+    }
+}
+
+impl SharedCache for BufferedLruCache<u64, u64> {
+    fn get(&self, key: u64) -> Option<u64> {
+        BufferedLruCache::get(self, &key)
+    }
+
+    fn put(&self, key: u64, value: u64) {
+        BufferedLruCache::put(self, key, value);
+    }
+}
+
+fn run<C: SharedCache>(name: &str, cache: C, threads: usize) {
+    for key in 0..CAPACITY {
+        cache.put(key, key);
+    }
+    let cache = Arc::new(cache);
+    let barrier = Arc::new(Barrier::new(threads + 1));
+    let handles: Vec<_> = (0..threads)
+        .map(|t| {
+            let cache = Arc::clone(&cache);
+            let barrier = Arc::clone(&barrier);
+            thread::spawn(move || {
+                let mut state = t as u64 * 0x9e37_79b9_7f4a_7c15 + 1;
+                barrier.wait();
+                for _ in 0..OPS_PER_THREAD {
+                    state = state
+                        .wrapping_mul(6364136223846793005)
+                        .wrapping_add(1442695040888963407);
+                    let key = (state >> 33) % KEY_SPACE;
+                    if black_box(cache.get(key)).is_none() {
+                        cache.put(key, key);
+                    }
+                }
+            })
+        })
+        .collect();
+
+    barrier.wait();
+    let start = Instant::now();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+    report(name, threads, start.elapsed());
+}
+
+fn report(name: &str, threads: usize, elapsed: Duration) {
+    let ops = OPS_PER_THREAD * threads as u64;
+    println!(
+        "{name:<24} threads={threads:<2} {:>8.2} Mops/s",
+        ops as f64 / elapsed.as_secs_f64() / 1e6
+    );
+}
+
+fn main() {
+    let capacity = CAPACITY as usize;
+    for threads in [1, 2, 4, 8] {
+        run("mutex", Mutex::new(LruCache::new(capacity)), threads);
+        run("sharded", ConcurrentLruCache::new(capacity), threads);
+        run("buffered", BufferedLruCache::new(capacity), threads);
+        println!();
+    }
+}
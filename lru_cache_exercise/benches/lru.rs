@@ -0,0 +1,47 @@
+//! Per-operation timings for `LruCache` hot paths.
+//!
+//! Run with `cargo bench --bench lru`.
+
+use std::hint::black_box;
+use std::time::Instant;
+
+use lru_cache_exercise::LruCache;
+
+const CAPACITY: u64 = 10_000;
+const OPS: u64 = 4_000_000;
+
+/// Deterministic key stream so runs are comparable.
+fn keys(space: u64) -> impl Iterator<Item = u64> {
+    let mut state = 0u64;
+    (0..OPS).map(move |_| {
+        state = state
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        (state >> 33) % space
+    })
+}
+
+fn get_or_put(name: &str, space: u64) {
+    let mut cache = LruCache::new(CAPACITY as usize);
+    for key in 0..CAPACITY {
+        cache.put(key, key);
+    }
+
+    let start = Instant::now();
+    for key in keys(space) {
+        if black_box(cache.get(&key)).is_none() {
+            cache.put(key, key);
+        }
+    }
+    let elapsed = start.elapsed();
+    println!(
+        "{name:<24} {:>8.1} ns/op",
+        elapsed.as_nanos() as f64 / OPS as f64
+    );
+}
+
+fn main() {
+    get_or_put("get_or_put/hit-95%", CAPACITY * 105 / 100);
+    get_or_put("get_or_put/hit-50%", CAPACITY * 2);
+    get_or_put("get_or_put/hit-0%", u64::MAX);
+}
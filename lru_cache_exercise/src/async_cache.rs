@@ -0,0 +1,430 @@
+//! An LRU cache for async code that coalesces concurrent loads of one key.
+
+use std::any::Any;
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::hash::Hash;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::task::{Context, Poll, Waker};
+
+use crate::cache::Displaced;
+use crate::policy::LruCache;
+use crate::stats::CacheStats;
+
+/// A thread-safe LRU cache whose misses are filled by async functions, at
+/// most one at a time per key.
+///
+/// When several tasks call [`get_with`](Self::get_with) for the same missing
+/// key, the first becomes the leader and runs its function; the others wait
+/// for its result instead of each calling the backend. If the leader's
+/// future is dropped before it finishes, one of the waiters takes over and
+/// runs its own function. Errors are handed to the tasks waiting on that
+/// load but are never cached.
+///
+/// The cache does not depend on any particular runtime. Its lock is never
+/// held across an `.await`.
+///
+/// ```
+/// use lru_cache_exercise::AsyncLruCache;
+///
+/// # fn block_on<F: std::future::Future>(future: F) -> F::Output {
+/// #     use std::task::{Context, Poll, Waker};
+/// #     let mut future = std::pin::pin!(future);
+/// #     let mut cx = Context::from_waker(Waker::noop());
+/// #     loop {
+/// #         if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+/// #             return output;
+/// #         }
+/// #     }
+/// # }
+/// let cache = AsyncLruCache::new(100);
+/// let user = block_on(cache.get_with(7, || async { Ok::<_, String>("ada".to_string()) }));
+/// assert_eq!(user.as_deref(), Ok("ada"));
+/// assert_eq!(cache.get(&7).as_deref(), Some("ada"));
+/// ```
+pub struct AsyncLruCache<K, V> {
+    inner: Mutex<Inner<K, V>>,
+}
+
+struct Inner<K, V> {
+    cache: LruCache<K, V>,
+    in_flight: HashMap<K, Arc<Flight<V>>>,
+}
+
+/// One load of one key, shared by its leader and waiters.
+struct Flight<V> {
+    state: Mutex<FlightState<V>>,
+}
+
+struct FlightState<V> {
+    outcome: Outcome<V>,
+    wakers: Vec<Waker>,
+}
+
+enum Outcome<V> {
+    Pending,
+    Loaded(V),
+    /// The leader's error, type-erased because each call to `get_with` may
+    /// use its own error type.
+    Failed(Arc<dyn Any + Send + Sync>),
+    /// The leader was dropped before finishing.
+    Abandoned,
+}
+
+impl<K, V> AsyncLruCache<K, V> {
+    /// Creates an empty cache that holds at most `capacity` entries.
+    pub fn new(capacity: usize) -> Self {
+        Self::from(LruCache::new(capacity))
+    }
+
+    /// Returns the number of cached entries. Loads in flight are not counted.
+    pub fn len(&self) -> usize {
+        self.lock().cache.len()
+    }
+
+    /// Returns `true` if no entries are cached.
+    pub fn is_empty(&self) -> bool {
+        self.lock().cache.is_empty()
+    }
+
+    /// Returns the hit, miss and eviction counts. A call that waits on
+    /// another task's load counts as a miss.
+    pub fn stats(&self) -> CacheStats {
+        self.lock().cache.stats()
+    }
+
+    /// Removes every cached entry. Loads in flight are not affected and will
+    /// still store their values.
+    pub fn clear(&self) {
+        self.lock().cache.clear();
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Inner<K, V>> {
+        self.inner.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<K: Hash + Eq + Clone, V: Clone> AsyncLruCache<K, V> {
+    /// Returns a clone of the cached value for `key`, without loading it.
+    pub fn get<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.lock().cache.get(key).cloned()
+    }
+
+    /// Returns the value for `key`, calling `init` and caching its result on
+    /// a miss.
+    ///
+    /// If another task is already loading `key`, this waits for that load
+    /// instead and `init` is not called. Should that task's future be dropped
+    /// before finishing, a waiting call takes over with its own `init`. An
+    /// error from the task that ran the load is returned to it and to every
+    /// call waiting on it, shared through an [`Arc`], and is not cached: the
+    /// next call for the key loads again. A waiter whose error type differs
+    /// from the leader's retries instead.
+    pub async fn get_with<F, Fut, E>(&self, key: K, init: F) -> Result<V, Arc<E>>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<V, E>>,
+        E: Send + Sync + 'static,
+    {
+        let flight = loop {
+            let flight = {
+                let mut inner = self.lock();
+                if let Some(value) = inner.cache.get(&key) {
+                    return Ok(value.clone());
+                }
+                match inner.in_flight.get(&key) {
+                    Some(flight) => Arc::clone(flight),
+                    None => {
+                        let flight = Arc::new(Flight::new());
+                        inner.in_flight.insert(key.clone(), Arc::clone(&flight));
+                        break flight;
+                    }
+                }
+            };
+            match (Wait { flight: &flight }).await {
+                Outcome::Loaded(value) => return Ok(value),
+                Outcome::Failed(error) => {
+                    if let Ok(error) = error.downcast::<E>() {
+                        return Err(error);
+                    }
+                }
+                Outcome::Abandoned | Outcome::Pending => {}
+            }
+        };
+
+        let mut leader = Leader {
+            cache: self,
+            key: &key,
+            flight,
+            outcome: Outcome::Abandoned,
+        };
+        match init().await {
+            Ok(value) => {
+                leader.outcome = Outcome::Loaded(value.clone());
+                let mut inner = self.lock();
+                inner.cache.put(key.clone(), value.clone());
+                leader.finish(&mut inner);
+                Ok(value)
+            }
+            Err(error) => {
+                let error = Arc::new(error);
+                leader.outcome = Outcome::Failed(Arc::clone(&error) as Arc<dyn Any + Send + Sync>);
+                Err(error)
+            }
+        }
+    }
+
+    /// Stores `value` for `key` directly. See [`LruCache::put`]. A load in
+    /// flight for the same key will overwrite it when it finishes.
+    pub fn put(&self, key: K, value: V) -> Vec<Displaced<K, V>> {
+        self.lock().cache.put(key, value)
+    }
+
+    /// Removes `key` from the cache, returning its value if it was present.
+    pub fn remove<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.lock().cache.remove(key)
+    }
+}
+
+impl<K, V> From<LruCache<K, V>> for AsyncLruCache<K, V> {
+    /// Wraps `cache`, keeping its entries, capacity, weigher, listener and
+    /// expiry settings.
+    fn from(cache: LruCache<K, V>) -> Self {
+        Self {
+            inner: Mutex::new(Inner {
+                cache,
+                in_flight: HashMap::new(),
+            }),
+        }
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug> fmt::Debug for AsyncLruCache<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let inner = self.lock();
+        f.debug_struct("AsyncLruCache")
+            .field("cache", &inner.cache)
+            .field("in_flight", &inner.in_flight.len())
+            .finish()
+    }
+}
+
+impl<V> Flight<V> {
+    fn new() -> Self {
+        Self {
+            state: Mutex::new(FlightState {
+                outcome: Outcome::Pending,
+                wakers: Vec::new(),
+            }),
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, FlightState<V>> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Resolves once a flight is no longer pending, to a copy of its outcome.
+struct Wait<'a, V> {
+    flight: &'a Flight<V>,
+}
+
+impl<V: Clone> Future for Wait<'_, V> {
+    type Output = Outcome<V>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Outcome<V>> {
+        let mut state = self.flight.state();
+        match &state.outcome {
+            Outcome::Pending => {
+                if !state.wakers.iter().any(|waker| waker.will_wake(cx.waker())) {
+                    state.wakers.push(cx.waker().clone());
+                }
+                Poll::Pending
+            }
+            Outcome::Loaded(value) => Poll::Ready(Outcome::Loaded(value.clone())),
+            Outcome::Failed(error) => Poll::Ready(Outcome::Failed(Arc::clone(error))),
+            Outcome::Abandoned => Poll::Ready(Outcome::Abandoned),
+        }
+    }
+}
+
+/// Held by the task running a load. However the load ends, including by the
+/// task's future being dropped, the flight is retired and its waiters woken.
+struct Leader<'a, K: Hash + Eq, V> {
+    cache: &'a AsyncLruCache<K, V>,
+    key: &'a K,
+    flight: Arc<Flight<V>>,
+    outcome: Outcome<V>,
+}
+
+impl<K: Hash + Eq, V> Leader<'_, K, V> {
+    /// Publishes the outcome and removes the flight, so that later callers
+    /// find either the cached value or no load at all.
+    fn finish(&mut self, inner: &mut Inner<K, V>) {
+        if inner
+            .in_flight
+            .get(self.key)
+            .is_some_and(|flight| Arc::ptr_eq(flight, &self.flight))
+        {
+            inner.in_flight.remove(self.key);
+        }
+        let wakers = {
+            let mut state = self.flight.state();
+            if !matches!(state.outcome, Outcome::Pending) {
+                return;
+            }
+            state.outcome = std::mem::replace(&mut self.outcome, Outcome::Abandoned);
+            std::mem::take(&mut state.wakers)
+        };
+        wakers.into_iter().for_each(Waker::wake);
+    }
+}
+
+impl<K: Hash + Eq, V> Drop for Leader<'_, K, V> {
+    fn drop(&mut self) {
+        let mut inner = self.cache.lock();
+        self.finish(&mut inner);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+    use tokio::sync::Notify;
+
+    /// A stand-in for a slow remote service that counts its calls.
+    #[derive(Default)]
+    struct MockBackend {
+        calls: AtomicUsize,
+        release: Notify,
+        fail_first: bool,
+    }
+
+    impl MockBackend {
+        /// Waits for `release` before answering, so that tests control when
+        /// loads finish.
+        async fn fetch(&self, key: u32) -> Result<String, String> {
+            let call = self.calls.fetch_add(1, Ordering::SeqCst);
+            self.release.notified().await;
+            if self.fail_first && call == 0 {
+                Err(format!("backend down for {key}"))
+            } else {
+                Ok(format!("value-{key}"))
+            }
+        }
+
+        fn calls(&self) -> usize {
+            self.calls.load(Ordering::SeqCst)
+        }
+    }
+
+    /// Polls until `condition` holds, yielding to other tasks in between.
+    async fn until(condition: impl Fn() -> bool) {
+        while !condition() {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+    }
+
+    fn spawn_get(
+        cache: &Arc<AsyncLruCache<u32, String>>,
+        backend: &Arc<MockBackend>,
+        key: u32,
+    ) -> tokio::task::JoinHandle<Result<String, Arc<String>>> {
+        let cache = Arc::clone(cache);
+        let backend = Arc::clone(backend);
+        tokio::spawn(async move { cache.get_with(key, || backend.fetch(key)).await })
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_misses_share_one_load() {
+        let cache = Arc::new(AsyncLruCache::new(16));
+        let backend = Arc::new(MockBackend::default());
+        let tasks: Vec<_> = (0..10).map(|_| spawn_get(&cache, &backend, 1)).collect();
+        until(|| backend.calls() == 1).await;
+        // Give every task time to join the flight before releasing it.
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        backend.release.notify_one();
+        for task in tasks {
+            assert_eq!(task.await.unwrap().as_deref(), Ok("value-1"));
+        }
+        assert_eq!(backend.calls(), 1);
+        assert_eq!(cache.get(&1).as_deref(), Some("value-1"));
+    }
+
+    #[tokio::test]
+    async fn test_different_keys_load_independently() {
+        let cache = Arc::new(AsyncLruCache::new(16));
+        let backend = Arc::new(MockBackend::default());
+        let first = spawn_get(&cache, &backend, 1);
+        let second = spawn_get(&cache, &backend, 2);
+        until(|| backend.calls() == 2).await;
+        backend.release.notify_waiters();
+        assert_eq!(first.await.unwrap().as_deref(), Ok("value-1"));
+        assert_eq!(second.await.unwrap().as_deref(), Ok("value-2"));
+    }
+
+    #[tokio::test]
+    async fn test_errors_reach_waiters_but_are_not_cached() {
+        let cache = Arc::new(AsyncLruCache::new(16));
+        let backend = Arc::new(MockBackend {
+            fail_first: true,
+            ..MockBackend::default()
+        });
+        let leader = spawn_get(&cache, &backend, 3);
+        until(|| backend.calls() == 1).await;
+        let waiter = spawn_get(&cache, &backend, 3);
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        backend.release.notify_one();
+
+        let expected = Err(Arc::new("backend down for 3".to_string()));
+        assert_eq!(leader.await.unwrap(), expected);
+        assert_eq!(waiter.await.unwrap(), expected);
+        assert_eq!(backend.calls(), 1);
+        assert!(cache.is_empty());
+
+        let retry = spawn_get(&cache, &backend, 3);
+        until(|| backend.calls() == 2).await;
+        backend.release.notify_one();
+        assert_eq!(retry.await.unwrap().as_deref(), Ok("value-3"));
+    }
+
+    #[tokio::test]
+    async fn test_waiter_takes_over_from_cancelled_leader() {
+        let cache = Arc::new(AsyncLruCache::new(16));
+        let backend = Arc::new(MockBackend::default());
+        let leader = spawn_get(&cache, &backend, 4);
+        until(|| backend.calls() == 1).await;
+        let waiter = spawn_get(&cache, &backend, 4);
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        leader.abort();
+        assert!(leader.await.unwrap_err().is_cancelled());
+        until(|| backend.calls() == 2).await;
+        backend.release.notify_one();
+        assert_eq!(waiter.await.unwrap().as_deref(), Ok("value-4"));
+        assert_eq!(cache.get(&4).as_deref(), Some("value-4"));
+    }
+
+    #[tokio::test]
+    async fn test_hits_do_not_call_init() {
+        let cache = AsyncLruCache::new(16);
+        cache.put(5, "cached".to_string());
+        let value = cache
+            .get_with(5, || async { Err::<String, _>("must not run") })
+            .await;
+        assert_eq!(value.as_deref(), Ok("cached"));
+    }
+}
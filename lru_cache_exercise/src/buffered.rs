@@ -0,0 +1,450 @@
+//! A thread-safe LRU cache whose reads take only a shared lock.
+
+use std::borrow::Borrow;
+use std::cell::Cell;
+use std::hash::Hash;
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard, TryLockError};
+use std::thread;
+
+use crate::cache::Displaced;
+use crate::policy::LruCache;
+use crate::stats::CacheStats;
+
+/// Records held by one read buffer before it must be drained. A power of two.
+const BUFFER_SIZE: usize = 16;
+
+/// An LRU cache for read-heavy workloads shared between threads.
+///
+/// A plain [`LruCache`] reorders its list on every hit, so sharing one
+/// between threads means taking an exclusive lock even to read. This cache
+/// instead serves hits under a shared [`RwLock`] and only records them in a
+/// small ring buffer, one of several striped by thread. When a buffer fills
+/// up, whichever reader filled it tries to take the write lock and applies
+/// every buffered hit to the list in one batch. Writes apply pending hits
+/// before making their own change.
+///
+/// Recency is therefore approximate. If the write lock is busy when a buffer
+/// is full, further hits recorded in it are dropped until it is drained, and
+/// hits are applied a little late. Both only affect which entry is evicted
+/// next, never which values are returned. A time-to-idle deadline is likewise
+/// pushed back when a buffered hit is applied rather than when it happens.
+///
+/// ```
+/// use std::sync::Arc;
+/// use std::thread;
+/// use lru_cache_exercise::BufferedLruCache;
+///
+/// let cache = Arc::new(BufferedLruCache::new(100));
+/// cache.put("config", 1);
+/// let readers: Vec<_> = (0..4)
+///     .map(|_| {
+///         let cache = Arc::clone(&cache);
+///         thread::spawn(move || cache.get("config"))
+///     })
+///     .collect();
+/// for reader in readers {
+///     assert_eq!(reader.join().unwrap(), Some(1));
+/// }
+/// ```
+#[derive(Debug)]
+pub struct BufferedLruCache<K, V> {
+    cache: RwLock<LruCache<K, V>>,
+    stripes: Box<[ReadBuffer]>,
+}
+
+impl<K, V> BufferedLruCache<K, V> {
+    /// Creates an empty cache that holds at most `capacity` entries.
+    ///
+    /// To set a weigher, listener or expiry, configure an [`LruCache`] and
+    /// convert it with [`From`].
+    pub fn new(capacity: usize) -> Self {
+        Self::from(LruCache::new(capacity))
+    }
+
+    /// Returns the number of entries in the cache.
+    pub fn len(&self) -> usize {
+        self.read().len()
+    }
+
+    /// Returns `true` if the cache holds no entries.
+    pub fn is_empty(&self) -> bool {
+        self.read().is_empty()
+    }
+
+    /// Returns the maximum total weight of the entries the cache can hold.
+    pub fn capacity(&self) -> usize {
+        self.read().capacity()
+    }
+
+    /// Returns the total weight of the entries currently in the cache.
+    pub fn current_weight(&self) -> usize {
+        self.read().current_weight()
+    }
+
+    /// Returns the hit, miss and eviction counts. Hits and misses are kept
+    /// per stripe and summed here.
+    pub fn stats(&self) -> CacheStats {
+        let mut stats: CacheStats = self
+            .stripes
+            .iter()
+            .map(|stripe| CacheStats {
+                hits: stripe.hits.load(Ordering::Relaxed),
+                misses: stripe.misses.load(Ordering::Relaxed),
+                evictions: 0,
+            })
+            .sum();
+        stats.evictions = self.read().stats().evictions;
+        stats
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, LruCache<K, V>> {
+        self.cache.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Returns the stripe the current thread records its hits in.
+    fn stripe(&self) -> &ReadBuffer {
+        &self.stripes[thread_probe() & (self.stripes.len() - 1)]
+    }
+}
+
+impl<K: Hash + Eq, V> BufferedLruCache<K, V> {
+    /// Returns a clone of the value for `key` and records the hit, to be
+    /// applied to the recency order later.
+    pub fn get<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        V: Clone,
+    {
+        let stripe = self.stripe();
+        let cache = self.read();
+        let Some((index, hash, value)) = cache.peek_indexed(key) else {
+            stripe.misses.fetch_add(1, Ordering::Relaxed);
+            return None;
+        };
+        let value = value.clone();
+        drop(cache);
+        stripe.hits.fetch_add(1, Ordering::Relaxed);
+        if !stripe.record(index, hash) {
+            self.try_drain();
+        }
+        Some(value)
+    }
+
+    /// Returns a clone of the value for `key` without recording a hit.
+    pub fn peek<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        V: Clone,
+    {
+        self.read().peek(key).cloned()
+    }
+
+    /// Returns `true` if `key` is present, without recording a hit.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.read().contains_key(key)
+    }
+
+    /// Inserts `value` for `key` as the most recently used entry. See
+    /// [`LruCache::put`].
+    pub fn put(&self, key: K, value: V) -> Vec<Displaced<K, V>> {
+        self.write().put(key, value)
+    }
+
+    /// Removes `key` from the cache, returning its value if it was present.
+    pub fn remove<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.write().remove(key)
+    }
+
+    /// Removes every entry, reporting each to the removal listener.
+    pub fn clear(&self) {
+        self.write().clear();
+    }
+
+    /// Removes every expired entry and returns how many were removed.
+    pub fn purge_expired(&self) -> usize {
+        self.write().purge_expired()
+    }
+
+    /// Applies every buffered hit to the recency order now, instead of
+    /// waiting for a buffer to fill or for the next write.
+    pub fn flush_reads(&self) {
+        drop(self.write());
+    }
+
+    /// Takes the write lock and applies pending hits under it.
+    fn write(&self) -> RwLockWriteGuard<'_, LruCache<K, V>> {
+        let mut cache = self.cache.write().unwrap_or_else(PoisonError::into_inner);
+        self.drain(&mut cache);
+        cache
+    }
+
+    /// Applies pending hits if the write lock is free. Otherwise whoever holds
+    /// it will drain the buffers soon enough.
+    fn try_drain(&self) {
+        let mut cache = match self.cache.try_write() {
+            Ok(cache) => cache,
+            Err(TryLockError::Poisoned(poisoned)) => poisoned.into_inner(),
+            Err(TryLockError::WouldBlock) => return,
+        };
+        self.drain(&mut cache);
+    }
+
+    fn drain(&self, cache: &mut LruCache<K, V>) {
+        for stripe in self.stripes.iter() {
+            stripe.drain(|index, hash_tag| cache.promote(index, hash_tag));
+        }
+    }
+}
+
+impl<K, V> From<LruCache<K, V>> for BufferedLruCache<K, V> {
+    /// Wraps `cache`, keeping its entries, capacity, weigher, listener and
+    /// expiry settings.
+    fn from(cache: LruCache<K, V>) -> Self {
+        let parallelism = thread::available_parallelism().map_or(1, NonZeroUsize::get);
+        let stripes = (0..(parallelism * 4).next_power_of_two())
+            .map(|_| ReadBuffer::new())
+            .collect();
+        Self {
+            cache: RwLock::new(cache),
+            stripes,
+        }
+    }
+}
+
+/// Returns a small number fixed for the current thread, used to pick its
+/// stripe.
+fn thread_probe() -> usize {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    thread_local! {
+        static PROBE: Cell<Option<usize>> = const { Cell::new(None) };
+    }
+    PROBE.with(|probe| {
+        probe.get().unwrap_or_else(|| {
+            let value = NEXT.fetch_add(1, Ordering::Relaxed);
+            probe.set(Some(value));
+            value
+        })
+    })
+}
+
+/// A bounded, lossy ring of pending hits with many writers and one reader.
+///
+/// Readers append under the cache's shared lock; the buffer is only drained
+/// under the exclusive lock, so there is never more than one consumer. Each
+/// record packs an arena index (plus one, so that zero marks an empty slot)
+/// above the low 32 bits of the key's hash. Aligned to keep stripes on
+/// separate cache lines.
+#[derive(Debug)]
+#[repr(align(128))]
+struct ReadBuffer {
+    /// Position of the next record to drain.
+    head: AtomicUsize,
+    /// Position of the next record to write.
+    tail: AtomicUsize,
+    records: [AtomicU64; BUFFER_SIZE],
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl ReadBuffer {
+    fn new() -> Self {
+        Self {
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+            records: std::array::from_fn(|_| AtomicU64::new(0)),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// Appends a hit on the entry at `index`. Returns `false` if the buffer
+    /// is full and should be drained; the hit is then dropped. A hit that
+    /// loses a race with another thread is dropped too, without asking for a
+    /// drain.
+    fn record(&self, index: usize, hash: u64) -> bool {
+        let Some(slot) = u32::try_from(index).ok().and_then(|i| i.checked_add(1)) else {
+            return true;
+        };
+        let head = self.head.load(Ordering::Acquire);
+        let tail = self.tail.load(Ordering::Relaxed);
+        if tail.wrapping_sub(head) >= BUFFER_SIZE {
+            return false;
+        }
+        if self
+            .tail
+            .compare_exchange(
+                tail,
+                tail.wrapping_add(1),
+                Ordering::AcqRel,
+                Ordering::Relaxed,
+            )
+            .is_ok()
+        {
+            let record = u64::from(slot) << 32 | (hash & 0xffff_ffff);
+            self.records[tail % BUFFER_SIZE].store(record, Ordering::Release);
+        }
+        true
+    }
+
+    /// Passes every complete record to `apply` as an index and hash tag.
+    /// Stops early at a slot that has been claimed but not yet written.
+    fn drain(&self, mut apply: impl FnMut(usize, u32)) {
+        let tail = self.tail.load(Ordering::Acquire);
+        let mut head = self.head.load(Ordering::Relaxed);
+        while head != tail {
+            let record = self.records[head % BUFFER_SIZE].swap(0, Ordering::Acquire);
+            if record == 0 {
+                break;
+            }
+            apply((record >> 32) as usize - 1, record as u32);
+            head = head.wrapping_add(1);
+        }
+        self.head.store(head, Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Barrier};
+
+    #[test]
+    fn test_is_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<BufferedLruCache<String, Vec<u8>>>();
+    }
+
+    #[test]
+    fn test_buffered_hits_protect_entries() {
+        let cache = BufferedLruCache::new(2);
+        cache.put(1, "one");
+        cache.put(2, "two");
+        assert_eq!(cache.get(&1), Some("one"));
+        // The write applies the pending hit on 1 before evicting.
+        assert_eq!(cache.put(3, "three"), [Displaced::Evicted(2, "two")]);
+        assert!(cache.contains_key(&1));
+    }
+
+    #[test]
+    fn test_peek_does_not_promote() {
+        let cache = BufferedLruCache::new(2);
+        cache.put(1, 1);
+        cache.put(2, 2);
+        assert_eq!(cache.peek(&1), Some(1));
+        cache.flush_reads();
+        assert_eq!(cache.put(3, 3), [Displaced::Evicted(1, 1)]);
+    }
+
+    #[test]
+    fn test_full_buffer_drops_hits_and_drains() {
+        let buffer = ReadBuffer::new();
+        for i in 0..BUFFER_SIZE {
+            assert!(buffer.record(i, i as u64));
+        }
+        assert!(!buffer.record(99, 99));
+        let mut seen = Vec::new();
+        buffer.drain(|index, tag| seen.push((index, tag)));
+        assert_eq!(seen.len(), BUFFER_SIZE);
+        assert_eq!(seen[3], (3, 3));
+        assert!(buffer.record(7, 7));
+    }
+
+    #[test]
+    fn test_stale_hits_are_ignored() {
+        let cache = BufferedLruCache::new(2);
+        cache.put(1, 1);
+        cache.put(2, 2);
+        assert_eq!(cache.get(&1), Some(1));
+        {
+            // Behind the buffer's back, hand 1's slot to key 3 and make 3
+            // the least recently used entry.
+            let mut inner = cache.cache.write().unwrap();
+            inner.remove(&1);
+            inner.put(3, 3);
+            inner.put(4, 4);
+        }
+        // Promoting the reused slot would wrongly save 3 and evict 4.
+        assert_eq!(cache.put(5, 5), [Displaced::Evicted(3, 3)]);
+    }
+
+    #[test]
+    fn test_stats_are_summed_over_stripes() {
+        let cache = Arc::new(BufferedLruCache::new(4));
+        cache.put(1, 1);
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let cache = Arc::clone(&cache);
+                thread::spawn(move || {
+                    for _ in 0..100 {
+                        cache.get(&1);
+                        cache.get(&2);
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses), (400, 400));
+    }
+
+    #[test]
+    fn test_stress_many_threads() {
+        const THREADS: usize = 16;
+        const OPS: usize = 20_000;
+        const CAPACITY: usize = 200;
+
+        let cache = Arc::new(BufferedLruCache::new(CAPACITY));
+        let barrier = Arc::new(Barrier::new(THREADS));
+        let handles: Vec<_> = (0..THREADS)
+            .map(|t| {
+                let cache = Arc::clone(&cache);
+                let barrier = Arc::clone(&barrier);
+                thread::spawn(move || {
+                    barrier.wait();
+                    let mut state = t as u64 + 1;
+                    for _ in 0..OPS {
+                        state ^= state << 13;
+                        state ^= state >> 7;
+                        state ^= state << 17;
+                        let key = state % 256;
+                        match state % 16 {
+                            0 => {
+                                cache.put(key, key + 1);
+                            }
+                            1 => {
+                                cache.remove(&key);
+                            }
+                            _ => {
+                                if let Some(value) = cache.get(&key) {
+                                    assert_eq!(value, key + 1);
+                                }
+                            }
+                        }
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        cache.flush_reads();
+        assert!(cache.len() <= CAPACITY);
+        let inner = cache.cache.read().unwrap();
+        assert_eq!(inner.iter().count(), inner.len());
+    }
+}
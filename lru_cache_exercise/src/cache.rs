@@ -0,0 +1,1314 @@
+//! A bounded map over an index-linked node arena, generic over its eviction
+//! policy.
+
+use std::borrow::Borrow;
+use std::hash::{BuildHasher, Hash};
+use std::time::{Duration, Instant};
+
+use hashbrown::HashTable;
+
+use crate::expiry::{Clock, Deadline, Expiry, SharedClock};
+use crate::listener::{Listener, RemovalCause, RemovalListener};
+use crate::policy::{Lru, Policy};
+use crate::stats::CacheStats;
+use crate::weigher::{EntryWeigher, Weigher};
+
+mod entry;
+mod iter;
+
+pub use entry::{Entry, OccupiedEntry, VacantEntry};
+pub use iter::{IntoIter, Iter, IterMut, Keys, Values, ValuesMut};
+
+type Link = Option<usize>;
+
+#[derive(Debug, Clone)]
+struct Node<K, V> {
+    key: K,
+    data: V,
+    weight: usize,
+    deadline: Option<Deadline>,
+}
+
+/// A slot in the node arena. Vacant slots are threaded into a free list so
+/// that evicted nodes are reused instead of growing the arena.
+#[derive(Debug, Clone)]
+enum Slot<K, V> {
+    Occupied(Node<K, V>),
+    Vacant { next_free: Link },
+}
+
+impl<K, V> Slot<K, V> {
+    fn node(&self) -> &Node<K, V> {
+        match self {
+            Slot::Occupied(node) => node,
+            Slot::Vacant { .. } => unreachable!("linked index points at a vacant slot"),
+        }
+    }
+
+    fn node_mut(&mut self) -> &mut Node<K, V> {
+        match self {
+            Slot::Occupied(node) => node,
+            Slot::Vacant { .. } => unreachable!("linked index points at a vacant slot"),
+        }
+    }
+}
+
+/// An entry pushed out of a [`Cache`] by [`put`](Cache::put).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Displaced<K, V> {
+    /// The key was already present; holds the value it replaced.
+    Replaced(V),
+    /// An entry that was evicted to make room.
+    Evicted(K, V),
+    /// The entry being inserted weighs more than the whole capacity, so it was
+    /// not stored. It is not counted as an eviction or passed to the removal
+    /// listener.
+    Rejected(K, V),
+}
+
+/// A map that holds at most `capacity` entries and evicts one chosen by its
+/// [`Policy`] to make room for a new key.
+///
+/// The policy defaults to [`Lru`], and [`LruCache`](crate::LruCache) names
+/// that combination; [`FifoCache`](crate::FifoCache) and
+/// [`RandomCache`](crate::RandomCache) are the other built-in ones. Both
+/// [`get`](Self::get) and [`put`](Self::put) report a use to the policy.
+/// Lookups accept any borrowed form of the key, as with
+/// [`HashMap`](std::collections::HashMap).
+///
+/// With a [`Weigher`] installed through [`with_weigher`](Self::with_weigher),
+/// the capacity bounds the total weight of the entries instead of their
+/// number.
+///
+/// Entries can also expire after a time-to-live or time-to-idle, set for the
+/// whole cache or per [`put_with_expiry`](Self::put_with_expiry). Expired
+/// entries are never returned by lookups; they are dropped lazily when a
+/// lookup or insertion runs into them, or eagerly by
+/// [`purge_expired`](Self::purge_expired). Until then they still count
+/// towards [`len`](Self::len) and show up in iterators.
+///
+/// Each key is stored once, in its node; the hash index only holds arena
+/// positions and compares keys through them.
+///
+/// ```
+/// use lru_cache_exercise::{FifoCache, LruCache};
+///
+/// let mut lru = LruCache::new(2);
+/// let mut fifo = FifoCache::new(2);
+/// for (key, value) in [("a", 1), ("b", 2)] {
+///     lru.put(key, value);
+///     fifo.put(key, value);
+/// }
+/// lru.get("a");
+/// fifo.get("a");
+/// lru.put("c", 3);
+/// fifo.put("c", 3);
+/// assert!(lru.contains_key("a"));
+/// assert!(!fifo.contains_key("a"));
+/// ```
+/// Hashes keys for the index and for the policies. Unit tests use fixed keys
+/// instead of random ones, so that policies whose decisions depend on hash
+/// collisions, such as TinyLFU's admission, behave the same on every run.
+#[cfg(not(test))]
+type KeyHasher = std::hash::RandomState;
+#[cfg(test)]
+type KeyHasher = std::hash::BuildHasherDefault<std::hash::DefaultHasher>;
+
+#[derive(Debug, Clone)]
+pub struct Cache<K, V, P = Lru> {
+    capacity: usize,
+    map: HashTable<usize>,
+    hasher: KeyHasher,
+    slots: Vec<Slot<K, V>>,
+    free: Link,
+    policy: P,
+    weight: usize,
+    weigher: EntryWeigher<K, V>,
+    listener: Listener<K, V>,
+    expiry: Expiry,
+    clock: SharedClock,
+    /// Set once any entry may carry a deadline, so that caches without
+    /// expiration never read the clock.
+    timed: bool,
+    stats: CacheStats,
+}
+
+impl<K, V, P: Policy + Default> Cache<K, V, P> {
+    /// Creates an empty cache that holds at most `capacity` entries.
+    ///
+    /// A zero-capacity cache is valid and never stores anything: [`put`]
+    /// hands every new entry straight back as [`Displaced::Rejected`].
+    ///
+    /// [`put`]: Self::put
+    pub fn new(capacity: usize) -> Self {
+        Self::with_policy(capacity, P::default())
+    }
+}
+
+impl<K, V, P: Policy> Cache<K, V, P> {
+    /// Creates an empty cache that holds at most `capacity` entries and
+    /// evicts according to `policy`, which must not track any slots yet.
+    pub fn with_policy(capacity: usize, mut policy: P) -> Self {
+        policy.set_capacity(capacity);
+        Self {
+            capacity,
+            map: HashTable::new(),
+            hasher: KeyHasher::default(),
+            slots: Vec::new(),
+            free: None,
+            policy,
+            weight: 0,
+            weigher: EntryWeigher::unit(),
+            listener: Listener::none(),
+            expiry: Expiry::NEVER,
+            clock: SharedClock::system(),
+            timed: false,
+            stats: CacheStats::default(),
+        }
+    }
+
+    /// Expires every entry `ttl` after its value was written, unless
+    /// overridden per entry.
+    pub fn with_time_to_live(mut self, ttl: Duration) -> Self {
+        self.expiry = self.expiry.with_time_to_live(ttl);
+        self.timed = true;
+        self
+    }
+
+    /// Expires every entry `tti` after it was last read or written, unless
+    /// overridden per entry. [`peek`](Self::peek) does not count as a read.
+    pub fn with_time_to_idle(mut self, tti: Duration) -> Self {
+        self.expiry = self.expiry.with_time_to_idle(tti);
+        self.timed = true;
+        self
+    }
+
+    /// Reads time from `clock` instead of the system clock.
+    pub fn with_clock(self, clock: impl Clock + Send + Sync + 'static) -> Self {
+        self.with_shared_clock(SharedClock::new(clock))
+    }
+
+    pub(crate) fn shared_clock(&self) -> SharedClock {
+        self.clock.clone()
+    }
+
+    pub(crate) fn with_shared_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Registers `listener` to be told about every entry that leaves the
+    /// cache, replacing any previous listener.
+    ///
+    /// ```
+    /// use std::sync::{Arc, Mutex};
+    /// use lru_cache_exercise::{LruCache, RemovalCause};
+    ///
+    /// let log = Arc::new(Mutex::new(Vec::new()));
+    /// let sink = Arc::clone(&log);
+    /// let mut cache = LruCache::new(1).with_removal_listener(
+    ///     move |key: &&'static str, value: &i32, cause: RemovalCause| {
+    ///         sink.lock().unwrap().push((*key, *value, cause));
+    ///     },
+    /// );
+    /// cache.put("a", 1);
+    /// cache.put("b", 2);
+    /// assert_eq!(*log.lock().unwrap(), [("a", 1, RemovalCause::Capacity)]);
+    /// ```
+    pub fn with_removal_listener(
+        self,
+        listener: impl RemovalListener<K, V> + Send + Sync + 'static,
+    ) -> Self {
+        self.with_shared_listener(Listener::new(listener))
+    }
+
+    pub(crate) fn with_shared_listener(mut self, listener: Listener<K, V>) -> Self {
+        self.listener = listener;
+        self
+    }
+
+    /// Returns the eviction policy.
+    pub fn policy(&self) -> &P {
+        &self.policy
+    }
+
+    pub(crate) fn expiry(&self) -> Expiry {
+        self.expiry
+    }
+
+    pub(crate) fn policy_mut(&mut self) -> &mut P {
+        &mut self.policy
+    }
+
+    /// Returns the number of entries in the cache.
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Returns `true` if the cache holds no entries.
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Returns the maximum total weight the cache holds. Without a custom
+    /// weigher this is the maximum number of entries.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the total weight of the entries in the cache. Without a custom
+    /// weigher this equals [`len`](Self::len).
+    pub fn current_weight(&self) -> usize {
+        self.weight
+    }
+
+    /// Returns the hit, miss and eviction counts since the cache was created
+    /// or the counts were last reset. Only [`get`](Self::get) and
+    /// [`get_mut`](Self::get_mut) count as lookups.
+    pub fn stats(&self) -> CacheStats {
+        self.stats
+    }
+
+    /// Resets the counters reported by [`stats`](Self::stats) to zero.
+    pub fn reset_stats(&mut self) {
+        self.stats = CacheStats::default();
+    }
+
+    /// Returns an iterator over the entries in the policy's order; for
+    /// [`Lru`], from most to least recently used. Iterating does not count as
+    /// a use.
+    pub fn iter(&self) -> Iter<'_, K, V, P> {
+        Iter::new(self)
+    }
+
+    /// Returns an iterator over the entries with mutable values, in the same
+    /// order as [`iter`](Self::iter). Iterating does not count as a use.
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V, P> {
+        IterMut::new(self)
+    }
+
+    /// Returns an iterator over the keys, in the same order as
+    /// [`iter`](Self::iter).
+    pub fn keys(&self) -> Keys<'_, K, V, P> {
+        Keys { inner: self.iter() }
+    }
+
+    /// Returns an iterator over the values, in the same order as
+    /// [`iter`](Self::iter).
+    pub fn values(&self) -> Values<'_, K, V, P> {
+        Values { inner: self.iter() }
+    }
+
+    /// Returns an iterator over mutable values, in the same order as
+    /// [`iter`](Self::iter).
+    pub fn values_mut(&mut self) -> ValuesMut<'_, K, V, P> {
+        ValuesMut {
+            inner: self.iter_mut(),
+        }
+    }
+
+    /// Removes every entry, keeping the allocated memory for reuse.
+    pub fn clear(&mut self) {
+        if self.listener.is_set() {
+            for (key, value) in self.iter() {
+                self.listener.notify(key, value, RemovalCause::Cleared);
+            }
+        }
+        self.map.clear();
+        self.slots.clear();
+        self.free = None;
+        self.policy.clear();
+        self.weight = 0;
+    }
+
+    /// Weighs an entry inserted through the entry API methods that have no
+    /// way to reject it.
+    fn weigh_for_entry(&self, key: &K, value: &V) -> usize {
+        let weight = self.weigher.weigh(key, value);
+        assert!(
+            weight <= self.capacity,
+            "entry weight {weight} exceeds the cache capacity {}",
+            self.capacity
+        );
+        weight
+    }
+
+    /// Returns the current time if any entry may have a deadline.
+    fn now(&self) -> Option<Instant> {
+        self.timed.then(|| self.clock.now())
+    }
+
+    /// Starts the expiration timers for a value written under `expiry`.
+    fn deadline(&mut self, expiry: Expiry) -> Option<Deadline> {
+        if expiry.is_never() {
+            return None;
+        }
+        self.timed = true;
+        Deadline::start(expiry, self.clock.now())
+    }
+
+    fn is_expired(&self, index: usize, now: Option<Instant>) -> bool {
+        match (self.node(index).deadline, now) {
+            (Some(deadline), Some(now)) => deadline.has_passed(now),
+            _ => false,
+        }
+    }
+
+    /// Returns whether the entry at `index`, which must be occupied, has
+    /// expired.
+    pub(crate) fn expired_at(&self, index: usize) -> bool {
+        self.is_expired(index, self.now())
+    }
+
+    /// Returns the value stored at `index`, which must be occupied.
+    pub(crate) fn value_at(&self, index: usize) -> &V {
+        &self.node(index).data
+    }
+
+    pub(crate) fn value_at_mut(&mut self, index: usize) -> &mut V {
+        &mut self.node_mut(index).data
+    }
+
+    /// Returns the key and value stored at `index`, which must be occupied.
+    pub(crate) fn entry_at(&self, index: usize) -> (&K, &V) {
+        let node = self.node(index);
+        (&node.key, &node.data)
+    }
+
+    fn node(&self, index: usize) -> &Node<K, V> {
+        self.slots[index].node()
+    }
+
+    fn node_mut(&mut self, index: usize) -> &mut Node<K, V> {
+        self.slots[index].node_mut()
+    }
+
+    /// Stores `node` in a free slot, reusing a vacated one if available.
+    fn allocate(&mut self, node: Node<K, V>) -> usize {
+        match self.free {
+            Some(index) => {
+                if let Slot::Vacant { next_free } = self.slots[index] {
+                    self.free = next_free;
+                }
+                self.slots[index] = Slot::Occupied(node);
+                index
+            }
+            None => {
+                self.slots.push(Slot::Occupied(node));
+                self.slots.len() - 1
+            }
+        }
+    }
+
+    /// Vacates the slot at `index` and returns the node it held. The policy
+    /// must already have stopped tracking the slot.
+    fn release(&mut self, index: usize) -> Node<K, V> {
+        let slot = std::mem::replace(
+            &mut self.slots[index],
+            Slot::Vacant {
+                next_free: self.free,
+            },
+        );
+        self.free = Some(index);
+        match slot {
+            Slot::Occupied(node) => node,
+            Slot::Vacant { .. } => unreachable!("released index {index} was already vacant"),
+        }
+    }
+}
+
+impl<K: Hash + Eq, V, P: Policy> Cache<K, V, P> {
+    /// Returns a reference to the value for `key` and reports the use to the
+    /// policy.
+    pub fn get<Q>(&mut self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.get_mut(key).map(|value| &*value)
+    }
+
+    /// Returns a mutable reference to the value for `key` and reports the use
+    /// to the policy.
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let index = self.lookup(key)?;
+        Some(&mut self.node_mut(index).data)
+    }
+
+    /// Does the work of [`get_mut`](Self::get_mut) but returns the arena
+    /// index, so that callers can decide how to borrow the value afterwards.
+    pub(crate) fn lookup<Q>(&mut self, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let Some(index) = self.find_live(self.hasher.hash_one(key), key) else {
+            self.stats.misses += 1;
+            return None;
+        };
+        self.stats.hits += 1;
+        self.touch(index);
+        Some(index)
+    }
+
+    /// Returns a reference to the value for `key` without reporting a use.
+    pub fn peek<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let index = self.find_unexpired(self.hasher.hash_one(key), key)?;
+        Some(&self.node(index).data)
+    }
+
+    /// Returns a mutable reference to the value for `key` without reporting a
+    /// use.
+    pub fn peek_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let index = self.find_unexpired(self.hasher.hash_one(key), key)?;
+        Some(&mut self.node_mut(index).data)
+    }
+
+    /// Returns `true` if the cache holds `key`. Does not count as a use.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.find_unexpired(self.hasher.hash_one(key), key)
+            .is_some()
+    }
+
+    /// Installs `weigher` so that the capacity bounds the total weight of the
+    /// entries rather than their number. Entries already in the cache are
+    /// re-weighed, and evicted if they no longer fit.
+    ///
+    /// Policies still see the capacity as a number of entries when they size
+    /// their internal queues; see [`Policy::set_capacity`].
+    ///
+    /// ```
+    /// use lru_cache_exercise::{Displaced, LruCache};
+    ///
+    /// let mut cache = LruCache::new(10).with_weigher(|_: &u32, blob: &Vec<u8>| blob.len());
+    /// cache.put(1, vec![0; 4]);
+    /// cache.put(2, vec![0; 4]);
+    /// assert_eq!(cache.current_weight(), 8);
+    /// assert_eq!(cache.put(3, vec![0; 4]), [Displaced::Evicted(1, vec![0; 4])]);
+    /// assert_eq!(cache.put(4, vec![0; 11]), [Displaced::Rejected(4, vec![0; 11])]);
+    /// ```
+    pub fn with_weigher(self, weigher: impl Weigher<K, V> + Send + Sync + 'static) -> Self {
+        self.with_shared_weigher(EntryWeigher::new(weigher))
+    }
+
+    pub(crate) fn with_shared_weigher(mut self, weigher: EntryWeigher<K, V>) -> Self {
+        self.weigher = weigher;
+        self.weight = 0;
+        for slot in &mut self.slots {
+            if let Slot::Occupied(node) = slot {
+                node.weight = self.weigher.weigh(&node.key, &node.data);
+                self.weight += node.weight;
+            }
+        }
+        self.evict_to_fit(0, None, |_, _| {});
+        self
+    }
+
+    /// Inserts `value` under `key`, replacing any previous value, and reports
+    /// the write to the policy. Entries chosen by the policy are evicted until
+    /// the new entry fits; the entry being written is never one of them.
+    ///
+    /// Returns everything the insertion pushed out of the cache: the replaced
+    /// value, then any evicted entries in the order they were evicted. An
+    /// entry heavier than the whole capacity is not stored and comes back as
+    /// [`Displaced::Rejected`]; an older value for its key is still removed.
+    ///
+    /// ```
+    /// use lru_cache_exercise::{Displaced, LruCache};
+    ///
+    /// let mut cache = LruCache::new(1);
+    /// assert_eq!(cache.put("a", 1), []);
+    /// assert_eq!(cache.put("a", 2), [Displaced::Replaced(1)]);
+    /// assert_eq!(cache.put("b", 3), [Displaced::Evicted("a", 2)]);
+    /// ```
+    pub fn put(&mut self, key: K, value: V) -> Vec<Displaced<K, V>> {
+        self.put_with_expiry(key, value, self.expiry)
+    }
+
+    /// Like [`put`](Self::put), but expires this entry according to `expiry`
+    /// instead of the cache-wide setting.
+    ///
+    /// ```
+    /// use std::sync::Arc;
+    /// use std::time::Duration;
+    /// use lru_cache_exercise::{Expiry, LruCache, ManualClock};
+    ///
+    /// let clock = Arc::new(ManualClock::new());
+    /// let mut cache = LruCache::new(8).with_clock(Arc::clone(&clock));
+    /// cache.put_with_expiry("token", 1, Expiry::NEVER.with_time_to_live(Duration::from_secs(5)));
+    /// cache.put("config", 2);
+    /// clock.advance(Duration::from_secs(5));
+    /// assert_eq!(cache.get("token"), None);
+    /// assert_eq!(cache.get("config"), Some(&2));
+    /// ```
+    pub fn put_with_expiry(&mut self, key: K, value: V, expiry: Expiry) -> Vec<Displaced<K, V>> {
+        let weight = self.weigher.weigh(&key, &value);
+        self.put_weighted(key, value, weight, expiry)
+    }
+
+    /// Like [`put_with_expiry`](Self::put_with_expiry), but with the entry's
+    /// weight given instead of taken from the weigher.
+    pub(crate) fn put_weighted(
+        &mut self,
+        key: K,
+        value: V,
+        weight: usize,
+        expiry: Expiry,
+    ) -> Vec<Displaced<K, V>> {
+        let mut displaced = Vec::new();
+        let hash = self.hasher.hash_one(&key);
+        let existing = self.find_live(hash, &key);
+
+        if weight > self.capacity {
+            if let Some(index) = existing {
+                let node = self.unlink(hash, index, RemovalCause::Replaced);
+                displaced.push(Displaced::Replaced(node.data));
+            }
+            displaced.push(Displaced::Rejected(key, value));
+            return displaced;
+        }
+
+        let deadline = self.deadline(expiry);
+        match existing {
+            Some(index) => {
+                let old = self.replace(index, value, weight, deadline);
+                displaced.push(Displaced::Replaced(old));
+                self.evict_to_fit(0, Some(index), |key, value| {
+                    displaced.push(Displaced::Evicted(key, value))
+                });
+            }
+            None => {
+                self.policy.before_insert(hash);
+                self.evict_to_fit(weight, None, |key, value| {
+                    displaced.push(Displaced::Evicted(key, value))
+                });
+                self.insert_new(hash, key, value, weight, deadline);
+            }
+        }
+        displaced
+    }
+
+    /// Removes every expired entry, reporting each to the removal listener
+    /// with [`RemovalCause::Expired`]. Returns how many were removed. Runs in
+    /// linear time.
+    pub fn purge_expired(&mut self) -> usize {
+        let Some(now) = self.now() else { return 0 };
+        let mut purged = 0;
+        for index in 0..self.slots.len() {
+            if matches!(self.slots[index], Slot::Occupied(_)) && self.is_expired(index, Some(now)) {
+                self.unlink_at(index, RemovalCause::Expired);
+                purged += 1;
+            }
+        }
+        purged
+    }
+
+    /// Changes the capacity, evicting entries chosen by the policy until the
+    /// cache fits. Returns the evicted entries in the order they were
+    /// evicted.
+    pub fn resize(&mut self, capacity: usize) -> Vec<(K, V)> {
+        self.capacity = capacity;
+        self.policy.set_capacity(capacity);
+        let mut evicted = Vec::new();
+        self.evict_to_fit(0, None, |key, value| evicted.push((key, value)));
+        evicted
+    }
+
+    /// Releases memory held for entries the cache no longer contains.
+    ///
+    /// Compacts the node arena so that it holds exactly [`len`](Self::len)
+    /// nodes and shrinks the hash index and the policy's state to match. Runs
+    /// in linear time.
+    pub fn shrink_to_fit(&mut self) {
+        let len = self.len();
+        let mut target = 0;
+        for index in len..self.slots.len() {
+            if let Slot::Vacant { .. } = self.slots[index] {
+                continue;
+            }
+            while let Slot::Occupied(_) = self.slots[target] {
+                target += 1;
+            }
+            self.slots.swap(target, index);
+            self.policy.relocate(index, target);
+        }
+        self.slots.truncate(len);
+        self.slots.shrink_to_fit();
+        self.free = None;
+        self.policy.shrink_to(len);
+
+        let mut map = HashTable::with_capacity(len);
+        for (index, slot) in self.slots.iter().enumerate() {
+            let hash = self.hasher.hash_one(&slot.node().key);
+            map.insert_unique(hash, index, |_| unreachable!("capacity was reserved"));
+        }
+        self.map = map;
+    }
+
+    /// Returns the entry for `key` for in-place manipulation.
+    ///
+    /// An occupied entry is reported to the policy as a use as soon as it is
+    /// returned. Inserting into a vacant entry may evict other entries,
+    /// exactly like [`put`](Self::put).
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V, P> {
+        let hash = self.hasher.hash_one(&key);
+        match self.find_live(hash, &key) {
+            Some(index) => {
+                self.touch(index);
+                Entry::Occupied(OccupiedEntry::new(self, hash, index))
+            }
+            None => Entry::Vacant(VacantEntry::new(self, hash, key)),
+        }
+    }
+
+    /// Removes `key` from the cache, returning its value if it was present.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let hash = self.hasher.hash_one(key);
+        let index = self.find_live(hash, key)?;
+        Some(self.unlink(hash, index, RemovalCause::Explicit).data)
+    }
+
+    /// Removes the entry at `index`, which must be occupied, as if by
+    /// [`remove`](Self::remove).
+    pub(crate) fn remove_at(&mut self, index: usize) -> (K, V) {
+        let node = self.unlink_at(index, RemovalCause::Explicit);
+        (node.key, node.data)
+    }
+
+    /// Drops the expired entry at `index`, reporting it to the removal
+    /// listener with [`RemovalCause::Expired`].
+    pub(crate) fn purge_at(&mut self, index: usize) {
+        self.unlink_at(index, RemovalCause::Expired);
+    }
+
+    /// Like [`peek`](Self::peek), but also returns the entry's arena index
+    /// and key hash so that a caller holding only a shared borrow can ask for
+    /// the entry to be promoted later.
+    pub(crate) fn peek_indexed<Q>(&self, key: &Q) -> Option<(usize, u64, &V)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let hash = self.hasher.hash_one(key);
+        let index = self.find_unexpired(hash, key)?;
+        Some((index, hash, &self.node(index).data))
+    }
+
+    /// Returns the hash the policy sees for `key`, whether or not the cache
+    /// holds it.
+    pub(crate) fn hash_of<Q: Hash + ?Sized>(&self, key: &Q) -> u64 {
+        self.hasher.hash_one(key)
+    }
+
+    /// Reports a use of the entry at `index`, provided the slot still holds a
+    /// key whose hash ends in the 32 bits of `hash_tag`. Stale requests, for
+    /// entries removed or replaced since they were looked up, are ignored.
+    pub(crate) fn promote(&mut self, index: usize, hash_tag: u32) {
+        let Some(Slot::Occupied(node)) = self.slots.get(index) else {
+            return;
+        };
+        if self.hasher.hash_one(&node.key) as u32 == hash_tag {
+            self.touch(index);
+        }
+    }
+
+    /// Looks up the arena index holding `key`.
+    fn find<Q>(&self, hash: u64, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+    {
+        let slots = &self.slots;
+        self.map
+            .find(hash, |&i| slots[i].node().key.borrow() == key)
+            .copied()
+    }
+
+    /// Like [`find`](Self::find), but treats an expired entry as absent.
+    fn find_unexpired<Q>(&self, hash: u64, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+    {
+        let index = self.find(hash, key)?;
+        (!self.is_expired(index, self.now())).then_some(index)
+    }
+
+    /// Like [`find`](Self::find), but removes the entry if it has expired.
+    fn find_live<Q>(&mut self, hash: u64, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+    {
+        let index = self.find(hash, key)?;
+        if self.is_expired(index, self.now()) {
+            self.unlink(hash, index, RemovalCause::Expired);
+            return None;
+        }
+        Some(index)
+    }
+
+    /// Records a use of the node at `index`: reports it to the policy and
+    /// restarts its idle timer.
+    fn touch(&mut self, index: usize) {
+        self.policy.on_hit(index);
+        if let (Some(now), Some(deadline)) = (self.now(), &mut self.node_mut(index).deadline) {
+            deadline.touch(now);
+        }
+    }
+
+    /// Evicts the policy's victims until `incoming` more weight fits within
+    /// the capacity, handing each evicted entry to `evicted`.
+    ///
+    /// The entry at `keep`, if any, is being written and must survive, so
+    /// victims are then chosen with [`Policy::victim_excluding`].
+    fn evict_to_fit(&mut self, incoming: usize, keep: Link, mut evicted: impl FnMut(K, V)) {
+        while self.weight + incoming > self.capacity {
+            let victim = match keep {
+                Some(keep) => self.policy.victim_excluding(keep),
+                None => self.policy.victim(),
+            };
+            let Some(victim) = victim else {
+                break;
+            };
+            let node = self.unlink_at(victim, RemovalCause::Capacity);
+            evicted(node.key, node.data);
+        }
+    }
+
+    /// Stores a key that is known to be absent, reports it to the policy and
+    /// returns the arena index of the new node. The caller must already have
+    /// made room for `weight`.
+    fn insert_new(
+        &mut self,
+        hash: u64,
+        key: K,
+        value: V,
+        weight: usize,
+        deadline: Option<Deadline>,
+    ) -> usize {
+        let index = self.allocate(Node {
+            key,
+            data: value,
+            weight,
+            deadline,
+        });
+        self.weight += weight;
+
+        self.policy.on_insert(index, hash);
+        let (slots, hasher) = (&self.slots, &self.hasher);
+        self.map
+            .insert_unique(hash, index, |&i| hasher.hash_one(&slots[i].node().key));
+        index
+    }
+
+    /// Swaps in a new value weighing `weight` for the node at `index`, reports
+    /// the write to the policy and returns the old value. The caller is
+    /// responsible for evicting if the cache is now over capacity.
+    fn replace(&mut self, index: usize, value: V, weight: usize, deadline: Option<Deadline>) -> V {
+        let node = self.node_mut(index);
+        node.deadline = deadline;
+        let old = std::mem::replace(&mut node.data, value);
+        let old_weight = std::mem::replace(&mut node.weight, weight);
+        self.weight = self.weight - old_weight + weight;
+        self.listener
+            .notify(&self.node(index).key, &old, RemovalCause::Replaced);
+        self.touch(index);
+        old
+    }
+
+    /// Like [`unlink`](Self::unlink), hashing the node's key to find it in the
+    /// index.
+    fn unlink_at(&mut self, index: usize, cause: RemovalCause) -> Node<K, V> {
+        let hash = self.hasher.hash_one(&self.node(index).key);
+        self.unlink(hash, index, cause)
+    }
+
+    /// Removes the node at `index`, whose key hashes to `hash`, from the index,
+    /// the policy and the arena, and reports it to the listener.
+    fn unlink(&mut self, hash: u64, index: usize, cause: RemovalCause) -> Node<K, V> {
+        if let Ok(entry) = self.map.find_entry(hash, |&i| i == index) {
+            entry.remove();
+        }
+        if cause == RemovalCause::Capacity {
+            self.policy.on_evict(index, hash);
+        } else {
+            self.policy.on_remove(index);
+        }
+        let node = self.release(index);
+        self.weight -= node.weight;
+        if cause.was_evicted() {
+            self.stats.evictions += 1;
+        }
+        self.listener.notify(&node.key, &node.data, cause);
+        node
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::policy::{FifoCache, LruCache};
+    use crate::ManualClock;
+    use std::rc::Rc;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_lru_cache() {
+        let mut cache = LruCache::new(2);
+        cache.put(1, 1);
+        cache.put(2, 2);
+        assert_eq!(cache.get(&1), Some(&1));
+        cache.put(3, 3);
+        assert_eq!(cache.get(&2), None);
+        cache.put(4, 4);
+        assert_eq!(cache.get(&1), None);
+        assert_eq!(cache.get(&3), Some(&3));
+        assert_eq!(cache.get(&4), Some(&4));
+    }
+
+    #[test]
+    fn test_evicted_slots_are_reused() {
+        let mut cache = LruCache::new(3);
+        for i in 0..100 {
+            cache.put(i, i);
+        }
+        assert_eq!(cache.slots.len(), 3);
+        assert_eq!(cache.get(&97), Some(&97));
+        assert_eq!(cache.get(&98), Some(&98));
+        assert_eq!(cache.get(&99), Some(&99));
+    }
+
+    #[test]
+    fn test_memory_is_released() {
+        let value = Rc::new(());
+        {
+            let mut cache = LruCache::new(2);
+            for i in 0..10 {
+                cache.put(i, value.clone());
+            }
+            // Only the two resident entries keep a reference alive.
+            assert_eq!(Rc::strong_count(&value), 3);
+        }
+        assert_eq!(Rc::strong_count(&value), 1);
+    }
+
+    #[test]
+    fn test_borrowed_key_lookups() {
+        let mut cache = LruCache::new(2);
+        cache.put("a".to_string(), 1);
+        cache.put("b".to_string(), 2);
+        assert!(cache.contains_key("a"));
+        assert_eq!(cache.get("a"), Some(&1));
+        *cache.get_mut("b").unwrap() += 10;
+        assert_eq!(cache.remove("b"), Some(12));
+        assert_eq!(cache.remove("b"), None);
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn test_get_mut_promotes() {
+        let mut cache = LruCache::new(2);
+        cache.put(1, 1);
+        cache.put(2, 2);
+        cache.get_mut(&1);
+        cache.put(3, 3);
+        assert!(cache.contains_key(&1));
+        assert!(!cache.contains_key(&2));
+    }
+
+    #[test]
+    fn test_remove_then_reinsert() {
+        let mut cache = LruCache::new(2);
+        cache.put(1, 1);
+        cache.put(2, 2);
+        cache.remove(&1);
+        cache.put(3, 3);
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get(&2), Some(&2));
+        assert_eq!(cache.get(&3), Some(&3));
+    }
+
+    #[test]
+    fn test_clear() {
+        let mut cache = LruCache::new(2);
+        cache.put(1, 1);
+        cache.put(2, 2);
+        cache.clear();
+        assert!(cache.is_empty());
+        assert_eq!(cache.capacity(), 2);
+        assert_eq!(cache.get(&1), None);
+        cache.put(3, 3);
+        assert_eq!(cache.get(&3), Some(&3));
+    }
+
+    #[test]
+    fn test_keys_and_values_need_not_be_clone() {
+        #[derive(Debug, PartialEq, Eq, Hash)]
+        struct Key(u32);
+        #[derive(Debug, PartialEq)]
+        struct Blob(Vec<u8>);
+
+        let mut cache = LruCache::new(1);
+        cache.put(Key(1), Blob(vec![1]));
+        assert_eq!(cache.get(&Key(1)), Some(&Blob(vec![1])));
+        cache.get_mut(&Key(1)).unwrap().0.push(2);
+        cache.put(Key(2), Blob(vec![3]));
+        assert_eq!(cache.get(&Key(1)), None);
+        assert_eq!(cache.remove(&Key(2)), Some(Blob(vec![3])));
+    }
+
+    #[test]
+    fn test_peek_does_not_promote() {
+        let mut cache = LruCache::new(2);
+        cache.put(1, 1);
+        cache.put(2, 2);
+        assert_eq!(cache.peek(&1), Some(&1));
+        *cache.peek_mut(&1).unwrap() = 10;
+        cache.put(3, 3);
+        assert_eq!(cache.peek(&1), None);
+        assert_eq!(cache.peek(&2), Some(&2));
+    }
+
+    #[test]
+    fn test_put_reports_displaced_entry() {
+        let mut cache = LruCache::new(2);
+        assert_eq!(cache.put(1, "a"), []);
+        assert_eq!(cache.put(2, "b"), []);
+        assert_eq!(cache.put(1, "c"), [Displaced::Replaced("a")]);
+        assert_eq!(cache.put(3, "d"), [Displaced::Evicted(2, "b")]);
+    }
+
+    #[test]
+    fn test_pop_and_peek_ends() {
+        let mut cache = LruCache::new(3);
+        assert_eq!(cache.peek_lru(), None);
+        assert_eq!(cache.pop_mru(), None);
+        cache.put(1, 1);
+        cache.put(2, 2);
+        cache.put(3, 3);
+        cache.get(&1);
+        assert_eq!(cache.peek_lru(), Some((&2, &2)));
+        assert_eq!(cache.peek_mru(), Some((&1, &1)));
+        assert_eq!(cache.pop_lru(), Some((2, 2)));
+        assert_eq!(cache.pop_mru(), Some((1, 1)));
+        assert_eq!(cache.len(), 1);
+        assert!(!cache.contains_key(&2));
+        assert_eq!(cache.pop_lru(), Some((3, 3)));
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_zero_capacity_stores_nothing() {
+        let mut cache = LruCache::new(0);
+        assert_eq!(cache.put(1, 1), [Displaced::Rejected(1, 1)]);
+        assert!(cache.is_empty());
+        assert_eq!(cache.get(&1), None);
+    }
+
+    #[test]
+    fn test_resize_shrinks_from_tail() {
+        let mut cache = LruCache::new(4);
+        for i in 1..=4 {
+            cache.put(i, i);
+        }
+        cache.get(&1);
+        assert_eq!(cache.resize(2), [(2, 2), (3, 3)]);
+        assert_eq!(cache.capacity(), 2);
+        assert_eq!(cache.keys().copied().collect::<Vec<_>>(), [1, 4]);
+        assert_eq!(cache.put(5, 5), [Displaced::Evicted(4, 4)]);
+
+        assert!(cache.resize(3).is_empty());
+        cache.put(6, 6);
+        assert_eq!(cache.len(), 3);
+
+        assert_eq!(cache.resize(0).len(), 3);
+        assert!(cache.is_empty());
+        assert_eq!(cache.put(7, 7), [Displaced::Rejected(7, 7)]);
+    }
+
+    #[test]
+    fn test_shrink_to_fit_compacts_arena() {
+        let mut cache = LruCache::new(100);
+        for i in 0..100 {
+            cache.put(i, i);
+        }
+        for i in 0..100 {
+            if i % 10 != 0 {
+                cache.remove(&i);
+            }
+        }
+        cache.get(&50);
+        cache.shrink_to_fit();
+        assert_eq!(cache.slots.len(), 10);
+        assert_eq!(
+            cache.keys().copied().collect::<Vec<_>>(),
+            [50, 90, 80, 70, 60, 40, 30, 20, 10, 0]
+        );
+        assert_eq!(cache.pop_lru(), Some((0, 0)));
+        cache.put(100, 100);
+        assert_eq!(cache.get(&90), Some(&90));
+        assert_eq!(cache.len(), 10);
+    }
+
+    #[test]
+    fn test_removal_listener_causes() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&log);
+        let mut cache = LruCache::new(2).with_removal_listener(
+            move |&key: &u32, &value: &u32, cause: RemovalCause| {
+                sink.lock().unwrap().push((key, value, cause));
+            },
+        );
+        cache.put(1, 10);
+        cache.put(1, 11);
+        cache.put(2, 20);
+        cache.put(3, 30);
+        cache.remove(&2);
+        cache.put(4, 40);
+        cache.pop_lru();
+        cache.put(5, 50);
+        cache.put(6, 60);
+        cache.resize(1);
+        cache.clear();
+
+        use RemovalCause::*;
+        assert_eq!(
+            *log.lock().unwrap(),
+            [
+                (1, 10, Replaced),
+                (1, 11, Capacity),
+                (2, 20, Explicit),
+                (3, 30, Explicit),
+                (4, 40, Capacity),
+                (5, 50, Capacity),
+                (6, 60, Cleared),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_weighted_put_evicts_until_it_fits() {
+        let mut cache = LruCache::new(10).with_weigher(|_: &u32, value: &usize| *value);
+        cache.put(1, 3);
+        cache.put(2, 3);
+        cache.put(3, 3);
+        assert_eq!(cache.current_weight(), 9);
+        assert_eq!(
+            cache.put(4, 7),
+            [Displaced::Evicted(1, 3), Displaced::Evicted(2, 3)]
+        );
+        assert_eq!(cache.current_weight(), 10);
+        assert_eq!(
+            cache.put(3, 4),
+            [Displaced::Replaced(3), Displaced::Evicted(4, 7)]
+        );
+        assert_eq!(cache.current_weight(), 4);
+        assert_eq!(cache.keys().copied().collect::<Vec<_>>(), [3]);
+    }
+
+    #[test]
+    fn test_overweight_entry_is_rejected() {
+        let mut cache = LruCache::new(10).with_weigher(|_: &u32, value: &usize| *value);
+        cache.put(1, 5);
+        cache.put(2, 5);
+        assert_eq!(cache.put(3, 11), [Displaced::Rejected(3, 11)]);
+        assert_eq!(cache.len(), 2);
+        assert_eq!(
+            cache.put(1, 11),
+            [Displaced::Replaced(5), Displaced::Rejected(1, 11)]
+        );
+        assert!(!cache.contains_key(&1));
+        assert_eq!(cache.current_weight(), 5);
+        assert_eq!(cache.stats().evictions, 0);
+    }
+
+    #[test]
+    fn test_weights_follow_removals_and_resize() {
+        let mut cache = LruCache::new(100).with_weigher(|_: &usize, value: &usize| *value);
+        for i in 1..=10 {
+            cache.put(i, i);
+        }
+        assert_eq!(cache.current_weight(), 55);
+        cache.remove(&10);
+        cache.pop_lru();
+        assert_eq!(cache.current_weight(), 44);
+        assert_eq!(
+            cache.resize(20),
+            [(2, 2), (3, 3), (4, 4), (5, 5), (6, 6), (7, 7)]
+        );
+        assert_eq!(cache.current_weight(), 17);
+        cache.clear();
+        assert_eq!(cache.current_weight(), 0);
+    }
+
+    #[test]
+    fn test_with_weigher_reweighs_existing_entries() {
+        let mut cache = LruCache::new(4);
+        for i in 1..=4 {
+            cache.put(i, i);
+        }
+        let cache = cache.with_weigher(|_: &i32, &value: &i32| value as usize);
+        assert_eq!(cache.keys().copied().collect::<Vec<_>>(), [4]);
+        assert_eq!(cache.current_weight(), 4);
+    }
+
+    #[test]
+    fn test_heavier_overwrite_does_not_evict_itself() {
+        let mut cache = FifoCache::new(10).with_weigher(|_: &u32, value: &usize| *value);
+        cache.put(1, 3);
+        cache.put(2, 3);
+        cache.put(3, 3);
+        assert_eq!(
+            cache.put(1, 8),
+            [
+                Displaced::Replaced(3),
+                Displaced::Evicted(2, 3),
+                Displaced::Evicted(3, 3),
+            ]
+        );
+        assert_eq!(cache.keys().copied().collect::<Vec<_>>(), [1]);
+        assert_eq!(cache.current_weight(), 8);
+    }
+
+    fn timed_cache(clock: &Arc<ManualClock>) -> LruCache<&'static str, u32> {
+        LruCache::new(8).with_clock(Arc::clone(clock))
+    }
+
+    #[test]
+    fn test_time_to_live_expires_on_get() {
+        let clock = Arc::new(ManualClock::new());
+        let mut cache = timed_cache(&clock).with_time_to_live(Duration::from_secs(10));
+        cache.put("a", 1);
+        clock.advance(Duration::from_secs(9));
+        assert_eq!(cache.get("a"), Some(&1));
+        clock.advance(Duration::from_secs(1));
+        assert!(!cache.contains_key("a"));
+        assert_eq!(cache.peek("a"), None);
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.get("a"), None);
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_time_to_idle_is_refreshed_by_get_not_peek() {
+        let clock = Arc::new(ManualClock::new());
+        let mut cache = timed_cache(&clock).with_time_to_idle(Duration::from_secs(5));
+        cache.put("a", 1);
+        cache.put("b", 2);
+        for _ in 0..3 {
+            clock.advance(Duration::from_secs(4));
+            assert_eq!(cache.get("a"), Some(&1));
+            cache.peek("b");
+        }
+        assert_eq!(cache.get("a"), Some(&1));
+        assert_eq!(cache.get("b"), None);
+    }
+
+    #[test]
+    fn test_put_with_expiry_overrides_default() {
+        let clock = Arc::new(ManualClock::new());
+        let mut cache = timed_cache(&clock).with_time_to_live(Duration::from_secs(10));
+        cache.put_with_expiry(
+            "short",
+            1,
+            Expiry::NEVER.with_time_to_live(Duration::from_secs(1)),
+        );
+        cache.put_with_expiry("forever", 2, Expiry::NEVER);
+        cache.put("default", 3);
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(cache.get("short"), None);
+        assert_eq!(cache.get("default"), Some(&3));
+        clock.advance(Duration::from_secs(100));
+        assert_eq!(cache.get("default"), None);
+        assert_eq!(cache.get("forever"), Some(&2));
+    }
+
+    #[test]
+    fn test_rewrite_restarts_time_to_live() {
+        let clock = Arc::new(ManualClock::new());
+        let mut cache = timed_cache(&clock).with_time_to_live(Duration::from_secs(10));
+        cache.put("a", 1);
+        clock.advance(Duration::from_secs(8));
+        assert_eq!(cache.put("a", 2), [Displaced::Replaced(1)]);
+        clock.advance(Duration::from_secs(8));
+        assert_eq!(cache.get("a"), Some(&2));
+    }
+
+    #[test]
+    fn test_expired_entries_are_reported_and_purged() {
+        let clock = Arc::new(ManualClock::new());
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&log);
+        let mut cache = timed_cache(&clock)
+            .with_time_to_live(Duration::from_secs(10))
+            .with_removal_listener(move |&key: &&'static str, _: &u32, cause| {
+                sink.lock().unwrap().push((key, cause));
+            });
+        cache.put("a", 1);
+        cache.put("b", 2);
+        clock.advance(Duration::from_secs(5));
+        cache.put("c", 3);
+        clock.advance(Duration::from_secs(5));
+
+        assert!(matches!(cache.entry("a"), Entry::Vacant(_)));
+        assert_eq!(cache.put("b", 20), []);
+        assert_eq!(cache.purge_expired(), 0);
+        clock.advance(Duration::from_secs(5));
+        assert_eq!(cache.purge_expired(), 1);
+        assert_eq!(cache.keys().copied().collect::<Vec<_>>(), ["b"]);
+        assert_eq!(
+            *log.lock().unwrap(),
+            [
+                ("a", RemovalCause::Expired),
+                ("b", RemovalCause::Expired),
+                ("c", RemovalCause::Expired),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_stats_count_hits_misses_and_evictions() {
+        let mut cache = LruCache::new(2);
+        cache.put(1, 1);
+        cache.put(2, 2);
+        cache.get(&1);
+        cache.get(&3);
+        cache.peek(&2);
+        cache.put(3, 3);
+        cache.remove(&1);
+        cache.resize(0);
+        cache.put(4, 4);
+        assert_eq!(
+            cache.stats(),
+            CacheStats {
+                hits: 1,
+                misses: 1,
+                evictions: 2,
+            }
+        );
+        assert_eq!(cache.stats().hit_rate(), 0.5);
+        cache.reset_stats();
+        assert_eq!(cache.stats(), CacheStats::default());
+    }
+}
@@ -0,0 +1,331 @@
+use std::fmt;
+use std::hash::Hash;
+
+use super::Cache;
+use crate::listener::RemovalCause;
+use crate::policy::{Lru, Policy};
+
+/// A view into a single entry of a [`Cache`], obtained from
+/// [`Cache::entry`].
+pub enum Entry<'a, K, V, P = Lru> {
+    /// The key is present. Its use has already been reported to the policy.
+    Occupied(OccupiedEntry<'a, K, V, P>),
+    /// The key is absent.
+    Vacant(VacantEntry<'a, K, V, P>),
+}
+
+/// An entry whose key is present in the cache.
+pub struct OccupiedEntry<'a, K, V, P = Lru> {
+    cache: &'a mut Cache<K, V, P>,
+    hash: u64,
+    index: usize,
+}
+
+/// An entry whose key is absent from the cache.
+pub struct VacantEntry<'a, K, V, P = Lru> {
+    cache: &'a mut Cache<K, V, P>,
+    hash: u64,
+    key: K,
+}
+
+impl<'a, K: Hash + Eq, V, P: Policy> Entry<'a, K, V, P> {
+    /// Returns the entry's key.
+    pub fn key(&self) -> &K {
+        match self {
+            Entry::Occupied(entry) => entry.key(),
+            Entry::Vacant(entry) => entry.key(),
+        }
+    }
+
+    /// Returns the value, inserting `default` if the key is absent.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the key is absent and `default` weighs more than the cache's
+    /// capacity, as [`VacantEntry::insert`] does. In a zero-capacity cache
+    /// that is any value. [`or_try_insert`](Self::or_try_insert) hands such
+    /// a value back instead.
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(default),
+        }
+    }
+
+    /// Returns the value, inserting `default` if the key is absent, or hands
+    /// back the key and `default` if they weigh more than the cache's
+    /// capacity.
+    pub fn or_try_insert(self, default: V) -> Result<&'a mut V, (K, V)> {
+        match self {
+            Entry::Occupied(entry) => Ok(entry.into_mut()),
+            Entry::Vacant(entry) => entry.try_insert(default),
+        }
+    }
+
+    /// Returns the value, inserting the result of `default` if the key is
+    /// absent.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the key is absent and the new value weighs more than the
+    /// cache's capacity, as [`VacantEntry::insert`] does.
+    pub fn or_insert_with<F: FnOnce() -> V>(self, default: F) -> &'a mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(default()),
+        }
+    }
+
+    /// Returns the value, inserting `V::default()` if the key is absent.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the key is absent and the default value weighs more than the
+    /// cache's capacity, as [`VacantEntry::insert`] does.
+    pub fn or_default(self) -> &'a mut V
+    where
+        V: Default,
+    {
+        self.or_insert_with(V::default)
+    }
+
+    /// Applies `f` to the value if the key is present.
+    pub fn and_modify<F: FnOnce(&mut V)>(self, f: F) -> Self {
+        match self {
+            Entry::Occupied(mut entry) => {
+                f(entry.get_mut());
+                Entry::Occupied(entry)
+            }
+            Entry::Vacant(entry) => Entry::Vacant(entry),
+        }
+    }
+}
+
+impl<'a, K: Hash + Eq, V, P: Policy> OccupiedEntry<'a, K, V, P> {
+    pub(super) fn new(cache: &'a mut Cache<K, V, P>, hash: u64, index: usize) -> Self {
+        Self { cache, hash, index }
+    }
+
+    /// Returns the entry's key.
+    pub fn key(&self) -> &K {
+        &self.cache.node(self.index).key
+    }
+
+    /// Returns a reference to the value.
+    pub fn get(&self) -> &V {
+        &self.cache.node(self.index).data
+    }
+
+    /// Returns a mutable reference to the value.
+    pub fn get_mut(&mut self) -> &mut V {
+        &mut self.cache.node_mut(self.index).data
+    }
+
+    /// Converts the entry into a mutable reference bound to the cache's
+    /// lifetime.
+    pub fn into_mut(self) -> &'a mut V {
+        &mut self.cache.node_mut(self.index).data
+    }
+
+    /// Replaces the value, returning the old one. Other entries may be
+    /// evicted if the new value is heavier; this one never is.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the new value weighs more than the cache's capacity.
+    pub fn insert(&mut self, value: V) -> V {
+        let weight = self.cache.weigh_for_entry(self.key(), &value);
+        let deadline = self.cache.deadline(self.cache.expiry);
+        let old = self.cache.replace(self.index, value, weight, deadline);
+        self.cache.evict_to_fit(0, Some(self.index), |_, _| {});
+        old
+    }
+
+    /// Removes the entry from the cache, returning its value.
+    pub fn remove(self) -> V {
+        self.remove_entry().1
+    }
+
+    /// Removes the entry from the cache, returning its key and value.
+    pub fn remove_entry(self) -> (K, V) {
+        let node = self
+            .cache
+            .unlink(self.hash, self.index, RemovalCause::Explicit);
+        (node.key, node.data)
+    }
+}
+
+impl<'a, K: Hash + Eq, V, P: Policy> VacantEntry<'a, K, V, P> {
+    pub(super) fn new(cache: &'a mut Cache<K, V, P>, hash: u64, key: K) -> Self {
+        Self { cache, hash, key }
+    }
+
+    /// Returns the key that would be inserted.
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// Takes ownership of the key without inserting.
+    pub fn into_key(self) -> K {
+        self.key
+    }
+
+    /// Inserts `value` and returns a mutable reference to it. Evicts entries
+    /// chosen by the policy until it fits.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the entry weighs more than the cache's capacity (for
+    /// instance, any entry in a zero-capacity cache), since the value could
+    /// not be kept to hand out a reference to it. Use
+    /// [`try_insert`](Self::try_insert) where that can happen.
+    pub fn insert(self, value: V) -> &'a mut V {
+        let weight = self.cache.weigh_for_entry(&self.key, &value);
+        self.insert_weighted(value, weight)
+    }
+
+    /// Like [`insert`](Self::insert), but hands back the key and value if
+    /// the entry weighs more than the cache's capacity, leaving the cache
+    /// untouched.
+    pub fn try_insert(self, value: V) -> Result<&'a mut V, (K, V)> {
+        let weight = self.cache.weigher.weigh(&self.key, &value);
+        if weight > self.cache.capacity {
+            return Err((self.key, value));
+        }
+        Ok(self.insert_weighted(value, weight))
+    }
+
+    fn insert_weighted(self, value: V, weight: usize) -> &'a mut V {
+        let deadline = self.cache.deadline(self.cache.expiry);
+        self.cache.policy.before_insert(self.hash);
+        self.cache.evict_to_fit(weight, None, |_, _| {});
+        let index = self
+            .cache
+            .insert_new(self.hash, self.key, value, weight, deadline);
+        &mut self.cache.node_mut(index).data
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug, P: Policy> fmt::Debug for Entry<'_, K, V, P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Entry::Occupied(entry) => f.debug_tuple("Entry").field(entry).finish(),
+            Entry::Vacant(entry) => f.debug_tuple("Entry").field(entry).finish(),
+        }
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug, P: Policy> fmt::Debug for OccupiedEntry<'_, K, V, P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let node = self.cache.node(self.index);
+        f.debug_struct("OccupiedEntry")
+            .field("key", &node.key)
+            .field("value", &node.data)
+            .finish()
+    }
+}
+
+impl<K: fmt::Debug, V, P> fmt::Debug for VacantEntry<'_, K, V, P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("VacantEntry").field(&self.key).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::policy::LruCache;
+
+    #[test]
+    fn test_or_insert_counts() {
+        let mut cache = LruCache::new(2);
+        for word in ["a", "b", "a", "a", "b"] {
+            *cache.entry(word).or_insert(0) += 1;
+        }
+        assert_eq!(cache.get(&"a"), Some(&3));
+        assert_eq!(cache.get(&"b"), Some(&2));
+    }
+
+    #[test]
+    fn test_occupied_entry_is_promoted() {
+        let mut cache = LruCache::new(2);
+        cache.put(1, 1);
+        cache.put(2, 2);
+        assert!(matches!(cache.entry(1), Entry::Occupied(_)));
+        cache.put(3, 3);
+        assert!(cache.contains_key(&1));
+        assert!(!cache.contains_key(&2));
+    }
+
+    #[test]
+    fn test_vacant_insert_evicts_tail() {
+        let mut cache = LruCache::new(2);
+        cache.put(1, 1);
+        cache.put(2, 2);
+        match cache.entry(3) {
+            Entry::Vacant(entry) => assert_eq!(*entry.insert(30), 30),
+            Entry::Occupied(_) => panic!("key 3 should be vacant"),
+        }
+        assert_eq!(cache.len(), 2);
+        assert!(!cache.contains_key(&1));
+        assert_eq!(cache.get(&3), Some(&30));
+    }
+
+    #[test]
+    fn test_and_modify_or_insert_with() {
+        let mut cache = LruCache::new(2);
+        cache
+            .entry("k".to_string())
+            .and_modify(|v| *v += 1)
+            .or_insert_with(|| 10);
+        cache
+            .entry("k".to_string())
+            .and_modify(|v| *v += 1)
+            .or_insert_with(|| 10);
+        assert_eq!(cache.get("k"), Some(&11));
+    }
+
+    #[test]
+    fn test_occupied_insert_and_remove() {
+        let mut cache = LruCache::new(2);
+        cache.put(1, "one");
+        let Entry::Occupied(mut entry) = cache.entry(1) else {
+            panic!("key 1 should be occupied");
+        };
+        assert_eq!(entry.insert("uno"), "one");
+        assert_eq!(entry.remove_entry(), (1, "uno"));
+        assert!(cache.is_empty());
+        cache.put(2, "two");
+        assert_eq!(cache.get(&2), Some(&"two"));
+    }
+
+    #[test]
+    #[should_panic(expected = "exceeds the cache capacity")]
+    fn test_vacant_insert_into_zero_capacity_panics() {
+        let mut cache = LruCache::new(0);
+        cache.entry(1).or_insert(1);
+    }
+
+    #[test]
+    fn test_try_insert_hands_back_what_cannot_fit() {
+        let mut cache = LruCache::new(0);
+        assert_eq!(cache.entry(1).or_try_insert(10), Err((1, 10)));
+        assert!(cache.is_empty());
+
+        let mut cache = LruCache::new(1);
+        cache.put(1, 10);
+        assert_eq!(cache.entry(1).or_try_insert(20), Ok(&mut 10));
+        let Entry::Vacant(entry) = cache.entry(2) else {
+            panic!("key 2 should be vacant");
+        };
+        assert_eq!(entry.try_insert(20), Ok(&mut 20));
+        assert_eq!(cache.keys().collect::<Vec<_>>(), [&2]);
+    }
+
+    #[test]
+    #[should_panic(expected = "exceeds the cache capacity")]
+    fn test_or_default_into_zero_capacity_panics() {
+        let mut cache = LruCache::<u32, u32>::new(0);
+        cache.entry(1).or_default();
+    }
+}
@@ -0,0 +1,454 @@
+use std::iter::FusedIterator;
+use std::marker::PhantomData;
+
+use super::{Cache, Link, Slot};
+use crate::policy::{Lru, Policy};
+
+/// An iterator over the entries of a [`Cache`], in the order of its policy.
+///
+/// Created by [`Cache::iter`].
+pub struct Iter<'a, K, V, P = Lru> {
+    slots: &'a [Slot<K, V>],
+    policy: &'a P,
+    front: Link,
+    back: Link,
+    len: usize,
+}
+
+impl<'a, K, V, P: Policy> Iter<'a, K, V, P> {
+    pub(super) fn new(cache: &'a Cache<K, V, P>) -> Self {
+        Self {
+            slots: &cache.slots,
+            policy: &cache.policy,
+            front: cache.policy.first(),
+            back: cache.policy.last(),
+            len: cache.len(),
+        }
+    }
+}
+
+impl<'a, K, V, P: Policy> Iterator for Iter<'a, K, V, P> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+        let index = self.front?;
+        let node = self.slots[index].node();
+        self.front = self.policy.next(index);
+        self.len -= 1;
+        Some((&node.key, &node.data))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<K, V, P: Policy> DoubleEndedIterator for Iter<'_, K, V, P> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+        let index = self.back?;
+        let node = self.slots[index].node();
+        self.back = self.policy.prev(index);
+        self.len -= 1;
+        Some((&node.key, &node.data))
+    }
+}
+
+impl<K, V, P: Policy> ExactSizeIterator for Iter<'_, K, V, P> {}
+
+impl<K, V, P: Policy> FusedIterator for Iter<'_, K, V, P> {}
+
+impl<K, V, P> Clone for Iter<'_, K, V, P> {
+    fn clone(&self) -> Self {
+        Self { ..*self }
+    }
+}
+
+/// A mutable iterator over the entries of a [`Cache`], in the order of its
+/// policy.
+///
+/// Created by [`Cache::iter_mut`].
+pub struct IterMut<'a, K, V, P = Lru> {
+    slots: *mut Slot<K, V>,
+    slots_len: usize,
+    policy: &'a P,
+    front: Link,
+    back: Link,
+    len: usize,
+    /// One bit per slot, set once the slot's entry has been lent out.
+    visited: Vec<u64>,
+    marker: PhantomData<&'a mut Slot<K, V>>,
+}
+
+impl<'a, K, V, P: Policy> IterMut<'a, K, V, P> {
+    pub(super) fn new(cache: &'a mut Cache<K, V, P>) -> Self {
+        Self {
+            front: cache.policy.first(),
+            back: cache.policy.last(),
+            len: cache.len(),
+            slots_len: cache.slots.len(),
+            slots: cache.slots.as_mut_ptr(),
+            policy: &cache.policy,
+            visited: Vec::new(),
+            marker: PhantomData,
+        }
+    }
+
+    /// Borrows the entry at `index` for the iterator's lifetime.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the policy yields a slot out of bounds or one this iterator
+    /// has already lent out, so a misbehaving policy can never alias a
+    /// value.
+    fn take(&mut self, index: usize) -> (&'a K, &'a mut V) {
+        assert!(
+            index < self.slots_len,
+            "policy yielded slot {index} out of bounds"
+        );
+        if self.visited.is_empty() {
+            self.visited = vec![0; self.slots_len.div_ceil(64)];
+        }
+        let (word, bit) = (index / 64, 1 << (index % 64));
+        assert!(
+            self.visited[word] & bit == 0,
+            "policy visited slot {index} twice"
+        );
+        self.visited[word] |= bit;
+        // SAFETY: `index` is in bounds and has not been lent out before, so
+        // this is the only borrow of its entry, and the cache stays borrowed
+        // mutably for `'a`.
+        let node = unsafe { (*self.slots.add(index)).node_mut() };
+        (&node.key, &mut node.data)
+    }
+}
+
+impl<'a, K, V, P: Policy> Iterator for IterMut<'a, K, V, P> {
+    type Item = (&'a K, &'a mut V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+        let index = self.front?;
+        self.front = self.policy.next(index);
+        self.len -= 1;
+        Some(self.take(index))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<K, V, P: Policy> DoubleEndedIterator for IterMut<'_, K, V, P> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+        let index = self.back?;
+        self.back = self.policy.prev(index);
+        self.len -= 1;
+        Some(self.take(index))
+    }
+}
+
+impl<K, V, P: Policy> ExactSizeIterator for IterMut<'_, K, V, P> {}
+
+impl<K, V, P: Policy> FusedIterator for IterMut<'_, K, V, P> {}
+
+// SAFETY: `IterMut` behaves like `&mut Cache<K, V, P>`.
+unsafe impl<K: Send, V: Send, P: Sync> Send for IterMut<'_, K, V, P> {}
+unsafe impl<K: Sync, V: Sync, P: Sync> Sync for IterMut<'_, K, V, P> {}
+
+/// An owning iterator over the entries of a [`Cache`], in the order of its
+/// policy.
+///
+/// Created by [`Cache::into_iter`](IntoIterator::into_iter).
+pub struct IntoIter<K, V, P = Lru> {
+    cache: Cache<K, V, P>,
+    len: usize,
+}
+
+impl<K, V, P: Policy> IntoIter<K, V, P> {
+    pub(super) fn new(cache: Cache<K, V, P>) -> Self {
+        Self {
+            len: cache.len(),
+            cache,
+        }
+    }
+
+    // The hash index is never consulted again, so nodes are dropped from the
+    // policy and the arena without being removed from it.
+    fn take(&mut self, index: usize) -> (K, V) {
+        self.cache.policy.on_remove(index);
+        let node = self.cache.release(index);
+        self.len -= 1;
+        (node.key, node.data)
+    }
+}
+
+impl<K, V, P: Policy> Iterator for IntoIter<K, V, P> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let first = self.cache.policy.first()?;
+        Some(self.take(first))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<K, V, P: Policy> DoubleEndedIterator for IntoIter<K, V, P> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let last = self.cache.policy.last()?;
+        Some(self.take(last))
+    }
+}
+
+impl<K, V, P: Policy> ExactSizeIterator for IntoIter<K, V, P> {}
+
+impl<K, V, P: Policy> FusedIterator for IntoIter<K, V, P> {}
+
+/// An iterator over the keys of a [`Cache`], in the order of its policy.
+///
+/// Created by [`Cache::keys`].
+pub struct Keys<'a, K, V, P = Lru> {
+    pub(super) inner: Iter<'a, K, V, P>,
+}
+
+impl<'a, K, V, P: Policy> Iterator for Keys<'a, K, V, P> {
+    type Item = &'a K;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(key, _)| key)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<K, V, P: Policy> DoubleEndedIterator for Keys<'_, K, V, P> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back().map(|(key, _)| key)
+    }
+}
+
+impl<K, V, P: Policy> ExactSizeIterator for Keys<'_, K, V, P> {}
+
+impl<K, V, P: Policy> FusedIterator for Keys<'_, K, V, P> {}
+
+impl<K, V, P> Clone for Keys<'_, K, V, P> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+/// An iterator over the values of a [`Cache`], in the order of its policy.
+///
+/// Created by [`Cache::values`].
+pub struct Values<'a, K, V, P = Lru> {
+    pub(super) inner: Iter<'a, K, V, P>,
+}
+
+impl<'a, K, V, P: Policy> Iterator for Values<'a, K, V, P> {
+    type Item = &'a V;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(_, value)| value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<K, V, P: Policy> DoubleEndedIterator for Values<'_, K, V, P> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back().map(|(_, value)| value)
+    }
+}
+
+impl<K, V, P: Policy> ExactSizeIterator for Values<'_, K, V, P> {}
+
+impl<K, V, P: Policy> FusedIterator for Values<'_, K, V, P> {}
+
+impl<K, V, P> Clone for Values<'_, K, V, P> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+/// A mutable iterator over the values of a [`Cache`], in the order of its
+/// policy.
+///
+/// Created by [`Cache::values_mut`].
+pub struct ValuesMut<'a, K, V, P = Lru> {
+    pub(super) inner: IterMut<'a, K, V, P>,
+}
+
+impl<'a, K, V, P: Policy> Iterator for ValuesMut<'a, K, V, P> {
+    type Item = &'a mut V;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(_, value)| value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<K, V, P: Policy> DoubleEndedIterator for ValuesMut<'_, K, V, P> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back().map(|(_, value)| value)
+    }
+}
+
+impl<K, V, P: Policy> ExactSizeIterator for ValuesMut<'_, K, V, P> {}
+
+impl<K, V, P: Policy> FusedIterator for ValuesMut<'_, K, V, P> {}
+
+impl<'a, K, V, P: Policy> IntoIterator for &'a Cache<K, V, P> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V, P>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, K, V, P: Policy> IntoIterator for &'a mut Cache<K, V, P> {
+    type Item = (&'a K, &'a mut V);
+    type IntoIter = IterMut<'a, K, V, P>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+impl<K, V, P: Policy> IntoIterator for Cache<K, V, P> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V, P>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter::new(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::policy::{Lru, LruCache, Policy};
+    use crate::Cache;
+
+    fn cache() -> LruCache<u32, u32> {
+        let mut cache = LruCache::new(4);
+        for i in 1..=4 {
+            cache.put(i, i * 10);
+        }
+        cache.get(&2);
+        cache
+    }
+
+    #[test]
+    fn test_iter_runs_most_to_least_recent() {
+        let cache = cache();
+        let keys: Vec<_> = cache.keys().copied().collect();
+        assert_eq!(keys, [2, 4, 3, 1]);
+        let values: Vec<_> = cache.values().rev().copied().collect();
+        assert_eq!(values, [10, 30, 40, 20]);
+        assert_eq!(cache.iter().len(), 4);
+    }
+
+    #[test]
+    fn test_iter_meets_in_the_middle() {
+        let cache = cache();
+        let mut iter = cache.iter();
+        assert_eq!(iter.next(), Some((&2, &20)));
+        assert_eq!(iter.next_back(), Some((&1, &10)));
+        assert_eq!(iter.next(), Some((&4, &40)));
+        assert_eq!(iter.next_back(), Some((&3, &30)));
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.next_back(), None);
+    }
+
+    #[test]
+    fn test_iter_mut_does_not_reorder() {
+        let mut cache = cache();
+        for (key, value) in &mut cache {
+            *value += key;
+        }
+        for value in cache.values_mut().rev() {
+            *value *= 2;
+        }
+        let entries: Vec<_> = cache.iter().map(|(&k, &v)| (k, v)).collect();
+        assert_eq!(entries, [(2, 44), (4, 88), (3, 66), (1, 22)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "policy visited slot")]
+    fn test_iter_mut_rejects_a_repeating_walk() {
+        /// An LRU list whose forward walk never moves past its first slot.
+        #[derive(Default)]
+        struct Stuck(Lru);
+
+        impl Policy for Stuck {
+            fn on_insert(&mut self, slot: usize, hash: u64) {
+                self.0.on_insert(slot, hash);
+            }
+            fn on_hit(&mut self, slot: usize) {
+                self.0.on_hit(slot);
+            }
+            fn on_remove(&mut self, slot: usize) {
+                self.0.on_remove(slot);
+            }
+            fn victim(&mut self) -> Option<usize> {
+                self.0.victim()
+            }
+            fn first(&self) -> Option<usize> {
+                self.0.first()
+            }
+            fn last(&self) -> Option<usize> {
+                self.0.last()
+            }
+            fn next(&self, slot: usize) -> Option<usize> {
+                Some(slot)
+            }
+            fn prev(&self, slot: usize) -> Option<usize> {
+                self.0.prev(slot)
+            }
+            fn relocate(&mut self, from: usize, to: usize) {
+                self.0.relocate(from, to);
+            }
+            fn clear(&mut self) {
+                self.0.clear();
+            }
+        }
+
+        let mut cache = Cache::<_, _, Stuck>::new(2);
+        cache.put(1, 10);
+        cache.put(2, 20);
+        let _aliased: Vec<_> = cache.iter_mut().collect();
+    }
+
+    #[test]
+    fn test_into_iter_both_ends() {
+        let mut iter = cache().into_iter();
+        assert_eq!(iter.len(), 4);
+        assert_eq!(iter.next(), Some((2, 20)));
+        assert_eq!(iter.next_back(), Some((1, 10)));
+        assert_eq!(iter.collect::<Vec<_>>(), [(4, 40), (3, 30)]);
+    }
+}
@@ -0,0 +1,407 @@
+//! A thread-safe LRU cache split into independently locked shards.
+
+use std::borrow::Borrow;
+use std::hash::{BuildHasher, Hash, RandomState};
+use std::num::NonZeroUsize;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::thread;
+use std::time::Duration;
+
+use crate::cache::Displaced;
+use crate::expiry::{Clock, SharedClock};
+use crate::listener::{Listener, RemovalListener};
+use crate::policy::LruCache;
+use crate::stats::CacheStats;
+use crate::weigher::{EntryWeigher, Weigher};
+
+/// An LRU cache that can be shared between threads.
+///
+/// Keys are spread over a fixed number of shards by hash. Each shard is an
+/// ordinary [`LruCache`] behind its own lock, so threads working on keys in
+/// different shards do not contend. The capacity is divided evenly among the
+/// shards, which makes eviction approximate: an entry is evicted when its own
+/// shard is full, even if other shards have room.
+///
+/// Every method takes `&self`. Lookups clone the value out, since a
+/// reference could not outlive the shard's lock; wrap large values in an
+/// [`Arc`](std::sync::Arc) to keep that cheap.
+///
+/// ```
+/// use std::sync::Arc;
+/// use std::thread;
+/// use lru_cache_exercise::ConcurrentLruCache;
+///
+/// let cache = Arc::new(ConcurrentLruCache::new(1024));
+/// let handles: Vec<_> = (0..4)
+///     .map(|t| {
+///         let cache = Arc::clone(&cache);
+///         thread::spawn(move || {
+///             for i in 0..100 {
+///                 cache.put(t * 100 + i, i);
+///             }
+///         })
+///     })
+///     .collect();
+/// for handle in handles {
+///     handle.join().unwrap();
+/// }
+/// assert_eq!(cache.len(), 400);
+/// assert_eq!(cache.get(&205), Some(5));
+/// ```
+#[derive(Debug)]
+pub struct ConcurrentLruCache<K, V> {
+    shards: Box<[Mutex<LruCache<K, V>>]>,
+    hasher: RandomState,
+}
+
+impl<K, V> ConcurrentLruCache<K, V> {
+    /// Creates an empty cache that holds at most `capacity` entries, with a
+    /// shard count chosen from the machine's available parallelism.
+    pub fn new(capacity: usize) -> Self {
+        let parallelism = thread::available_parallelism().map_or(1, NonZeroUsize::get);
+        Self::with_shards(capacity, (parallelism * 4).next_power_of_two())
+    }
+
+    /// Creates an empty cache that holds at most `capacity` entries, split
+    /// over `shards` shards.
+    ///
+    /// The shard count is reduced so that every shard can hold at least one
+    /// entry, and is always at least one.
+    pub fn with_shards(capacity: usize, shards: usize) -> Self {
+        let count = shards.min(capacity).max(1);
+        let shards = (0..count)
+            .map(|i| {
+                let extra = usize::from(i < capacity % count);
+                Mutex::new(LruCache::new(capacity / count + extra))
+            })
+            .collect();
+        Self {
+            shards,
+            hasher: RandomState::new(),
+        }
+    }
+
+    /// Expires every entry `ttl` after its value was written.
+    pub fn with_time_to_live(self, ttl: Duration) -> Self {
+        self.configure(|shard| shard.with_time_to_live(ttl))
+    }
+
+    /// Expires every entry once it has gone `tti` without being read or
+    /// written.
+    pub fn with_time_to_idle(self, tti: Duration) -> Self {
+        self.configure(|shard| shard.with_time_to_idle(tti))
+    }
+
+    /// Reads time from `clock` instead of the system clock. All shards share
+    /// the same clock.
+    pub fn with_clock(self, clock: impl Clock + Send + Sync + 'static) -> Self {
+        let clock = SharedClock::new(clock);
+        self.configure(|shard| shard.with_shared_clock(clock.clone()))
+    }
+
+    /// Calls `listener` for every entry that leaves the cache. The listener
+    /// is shared by all shards and runs while the entry's shard is locked, so
+    /// it must not call back into the cache.
+    pub fn with_removal_listener(
+        self,
+        listener: impl RemovalListener<K, V> + Send + Sync + 'static,
+    ) -> Self {
+        let listener = Listener::new(listener);
+        self.configure(|shard| shard.with_shared_listener(listener.clone()))
+    }
+
+    /// Returns the number of entries across all shards.
+    ///
+    /// Shards are locked one at a time, so under concurrent writes the result
+    /// is a snapshot of each shard taken at slightly different moments.
+    pub fn len(&self) -> usize {
+        self.shards.iter().map(|shard| lock(shard).len()).sum()
+    }
+
+    /// Returns `true` if every shard is empty.
+    pub fn is_empty(&self) -> bool {
+        self.shards.iter().all(|shard| lock(shard).is_empty())
+    }
+
+    /// Returns the total capacity of all shards.
+    pub fn capacity(&self) -> usize {
+        self.shards.iter().map(|shard| lock(shard).capacity()).sum()
+    }
+
+    /// Returns the number of shards.
+    pub fn shard_count(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// Returns the total weight of the entries across all shards.
+    pub fn current_weight(&self) -> usize {
+        self.shards
+            .iter()
+            .map(|shard| lock(shard).current_weight())
+            .sum()
+    }
+
+    /// Returns the hit, miss and eviction counts summed over all shards.
+    pub fn stats(&self) -> CacheStats {
+        self.shards.iter().map(|shard| lock(shard).stats()).sum()
+    }
+
+    /// Resets the counters reported by [`stats`](Self::stats) to zero.
+    pub fn reset_stats(&self) {
+        for shard in self.shards.iter() {
+            lock(shard).reset_stats();
+        }
+    }
+
+    /// Removes every entry, reporting each to the removal listener.
+    pub fn clear(&self) {
+        for shard in self.shards.iter() {
+            lock(shard).clear();
+        }
+    }
+
+    /// Rebuilds every shard through `f`, for the builder methods.
+    fn configure(self, mut f: impl FnMut(LruCache<K, V>) -> LruCache<K, V>) -> Self {
+        let shards = self
+            .shards
+            .into_vec()
+            .into_iter()
+            .map(|shard| {
+                let shard = shard.into_inner().unwrap_or_else(PoisonError::into_inner);
+                Mutex::new(f(shard))
+            })
+            .collect();
+        Self { shards, ..self }
+    }
+}
+
+impl<K: Hash + Eq, V> ConcurrentLruCache<K, V> {
+    /// Bounds each shard by the total weight of its entries, as computed by
+    /// `weigher`, instead of their number.
+    ///
+    /// Each shard gets its share of the capacity, so a single entry heavier
+    /// than a shard's share is rejected even if it fits the total.
+    pub fn with_weigher(self, weigher: impl Weigher<K, V> + Send + Sync + 'static) -> Self {
+        let weigher = EntryWeigher::new(weigher);
+        self.configure(|shard| shard.with_shared_weigher(weigher.clone()))
+    }
+
+    /// Returns a clone of the value for `key` and marks it as most recently
+    /// used within its shard.
+    pub fn get<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        V: Clone,
+    {
+        self.shard(key).get(key).cloned()
+    }
+
+    /// Returns a clone of the value for `key` without marking it as recently
+    /// used.
+    pub fn peek<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        V: Clone,
+    {
+        self.shard(key).peek(key).cloned()
+    }
+
+    /// Returns `true` if `key` is present, without marking it as recently
+    /// used.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.shard(key).contains_key(key)
+    }
+
+    /// Inserts `value` for `key` as the most recently used entry of its
+    /// shard, returning whatever left the shard as a result. See
+    /// [`LruCache::put`].
+    pub fn put(&self, key: K, value: V) -> Vec<Displaced<K, V>> {
+        let mut shard = self.shard(&key);
+        shard.put(key, value)
+    }
+
+    /// Removes `key` from the cache, returning its value if it was present.
+    pub fn remove<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.shard(key).remove(key)
+    }
+
+    /// Removes every expired entry from every shard and returns how many
+    /// were removed.
+    pub fn purge_expired(&self) -> usize {
+        self.shards
+            .iter()
+            .map(|shard| lock(shard).purge_expired())
+            .sum()
+    }
+
+    /// Locks the shard that owns `key`.
+    fn shard<Q: Hash + ?Sized>(&self, key: &Q) -> MutexGuard<'_, LruCache<K, V>> {
+        let hash = self.hasher.hash_one(key);
+        // The high bits, since each shard's table consumes the low ones of
+        // its own hash.
+        let index = ((hash >> 32) as usize) % self.shards.len();
+        lock(&self.shards[index])
+    }
+}
+
+/// Locks a shard, ignoring poisoning. A panic in a listener or weigher
+/// leaves the shard's list and table consistent, so the data is still
+/// usable.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RemovalCause;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Barrier};
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn test_is_send_and_sync() {
+        assert_send_sync::<ConcurrentLruCache<String, Vec<u8>>>();
+    }
+
+    #[test]
+    fn test_capacity_is_split_across_shards() {
+        let cache: ConcurrentLruCache<u32, u32> = ConcurrentLruCache::with_shards(10, 4);
+        assert_eq!(cache.shard_count(), 4);
+        assert_eq!(cache.capacity(), 10);
+
+        let tiny: ConcurrentLruCache<u32, u32> = ConcurrentLruCache::with_shards(3, 16);
+        assert_eq!(tiny.shard_count(), 3);
+        assert_eq!(tiny.capacity(), 3);
+
+        let empty: ConcurrentLruCache<u32, u32> = ConcurrentLruCache::with_shards(0, 16);
+        assert_eq!(empty.shard_count(), 1);
+        assert!(!empty.put(1, 1).is_empty());
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn test_single_shard_behaves_like_lru() {
+        let cache = ConcurrentLruCache::with_shards(2, 1);
+        cache.put(1, "one");
+        cache.put(2, "two");
+        assert_eq!(cache.get(&1), Some("one"));
+        assert_eq!(cache.put(3, "three"), [Displaced::Evicted(2, "two")]);
+        assert_eq!(cache.peek(&3), Some("three"));
+        assert_eq!(cache.remove(&1), Some("one"));
+        assert!(!cache.contains_key(&1));
+    }
+
+    #[test]
+    fn test_stats_and_listener_are_aggregated() {
+        let evicted = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&evicted);
+        let cache = ConcurrentLruCache::with_shards(8, 4).with_removal_listener(
+            move |_: &u32, _: &u32, cause: RemovalCause| {
+                if cause == RemovalCause::Capacity {
+                    counter.fetch_add(1, Ordering::Relaxed);
+                }
+            },
+        );
+        for i in 0..100 {
+            cache.put(i, i);
+        }
+        for i in 0..100 {
+            cache.get(&i);
+        }
+        let stats = cache.stats();
+        assert_eq!(cache.len(), 8);
+        assert_eq!(stats.requests(), 100);
+        assert_eq!(stats.hits, 8);
+        assert_eq!(stats.evictions, 92);
+        assert_eq!(evicted.load(Ordering::Relaxed), 92);
+        cache.reset_stats();
+        assert_eq!(cache.stats(), CacheStats::default());
+    }
+
+    #[test]
+    fn test_weigher_and_expiry_apply_to_every_shard() {
+        let clock = Arc::new(crate::ManualClock::new());
+        let cache = ConcurrentLruCache::with_shards(40, 4)
+            .with_weigher(|_: &u32, v: &u32| *v as usize)
+            .with_clock(Arc::clone(&clock))
+            .with_time_to_live(Duration::from_secs(1));
+        for i in 0..16 {
+            cache.put(i, 2);
+        }
+        assert!(cache.current_weight() <= 40);
+        assert!(matches!(
+            cache.put(99, 11)[..],
+            [Displaced::Rejected(99, 11)]
+        ));
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(cache.get(&0), None);
+        let stored = cache.len();
+        assert_eq!(cache.purge_expired(), stored);
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_stress_many_threads() {
+        const THREADS: usize = 16;
+        const OPS: usize = 20_000;
+        const CAPACITY: usize = 256;
+
+        let cache = Arc::new(ConcurrentLruCache::with_shards(CAPACITY, 8));
+        let barrier = Arc::new(Barrier::new(THREADS));
+        let handles: Vec<_> = (0..THREADS)
+            .map(|t| {
+                let cache = Arc::clone(&cache);
+                let barrier = Arc::clone(&barrier);
+                thread::spawn(move || {
+                    barrier.wait();
+                    let mut state = t as u64 + 1;
+                    for _ in 0..OPS {
+                        // xorshift keeps the key sequence cheap and varied.
+                        state ^= state << 13;
+                        state ^= state >> 7;
+                        state ^= state << 17;
+                        let key = state % 1024;
+                        match state % 4 {
+                            0 => {
+                                cache.put(key, key * 3);
+                            }
+                            1 => {
+                                cache.remove(&key);
+                            }
+                            _ => {
+                                if let Some(value) = cache.get(&key) {
+                                    assert_eq!(value, key * 3);
+                                }
+                            }
+                        }
+                        assert!(cache.len() <= CAPACITY);
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert!(cache.len() <= CAPACITY);
+        let stats = cache.stats();
+        assert!(stats.requests() > 0);
+        for key in 0..1024 {
+            if let Some(value) = cache.peek(&key) {
+                assert_eq!(value, key * 3);
+            }
+        }
+    }
+}
@@ -0,0 +1,232 @@
+//! Time-based expiration and the clocks that drive it.
+
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// A source of the current time.
+///
+/// Caches read time through this trait so that tests can substitute a
+/// [`ManualClock`] and advance it deterministically instead of sleeping.
+pub trait Clock {
+    /// Returns the current instant. Must never go backwards.
+    fn now(&self) -> Instant;
+}
+
+impl<C: Clock + ?Sized> Clock for Arc<C> {
+    fn now(&self) -> Instant {
+        (**self).now()
+    }
+}
+
+/// The real monotonic clock, [`Instant::now`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// A clock that only moves when told to.
+///
+/// Share it with a cache through an [`Arc`] and call
+/// [`advance`](Self::advance) to make time pass.
+///
+/// ```
+/// use std::sync::Arc;
+/// use std::time::Duration;
+/// use lru_cache_exercise::{LruCache, ManualClock};
+///
+/// let clock = Arc::new(ManualClock::new());
+/// let mut cache = LruCache::new(8)
+///     .with_clock(Arc::clone(&clock))
+///     .with_time_to_live(Duration::from_secs(60));
+/// cache.put("session", 1);
+/// clock.advance(Duration::from_secs(61));
+/// assert_eq!(cache.get("session"), None);
+/// ```
+#[derive(Debug)]
+pub struct ManualClock {
+    origin: Instant,
+    elapsed_nanos: AtomicU64,
+}
+
+impl ManualClock {
+    /// Creates a clock stopped at the current instant.
+    pub fn new() -> Self {
+        Self {
+            origin: Instant::now(),
+            elapsed_nanos: AtomicU64::new(0),
+        }
+    }
+
+    /// Moves the clock forward by `duration`.
+    pub fn advance(&self, duration: Duration) {
+        let nanos = u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX);
+        self.elapsed_nanos.fetch_add(nanos, Ordering::SeqCst);
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        self.origin + Duration::from_nanos(self.elapsed_nanos.load(Ordering::SeqCst))
+    }
+}
+
+/// How long an entry may stay in a cache.
+///
+/// Either limit may be left unset. When both are set the entry expires at
+/// whichever deadline comes first.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Expiry {
+    /// Maximum time since the entry's value was written.
+    pub time_to_live: Option<Duration>,
+    /// Maximum time since the entry was last read or written.
+    pub time_to_idle: Option<Duration>,
+}
+
+impl Expiry {
+    /// Entries that never expire.
+    pub const NEVER: Expiry = Expiry {
+        time_to_live: None,
+        time_to_idle: None,
+    };
+
+    /// Returns a copy with the time-to-live set to `ttl`.
+    pub fn with_time_to_live(self, ttl: Duration) -> Self {
+        Self {
+            time_to_live: Some(ttl),
+            ..self
+        }
+    }
+
+    /// Returns a copy with the time-to-idle set to `tti`.
+    pub fn with_time_to_idle(self, tti: Duration) -> Self {
+        Self {
+            time_to_idle: Some(tti),
+            ..self
+        }
+    }
+
+    /// Returns `true` if neither limit is set.
+    pub fn is_never(&self) -> bool {
+        self.time_to_live.is_none() && self.time_to_idle.is_none()
+    }
+}
+
+/// The moment a cached entry expires, kept up to date as it is accessed.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Deadline {
+    at: Instant,
+    live_until: Option<Instant>,
+    idle: Option<Duration>,
+}
+
+impl Deadline {
+    /// Starts the timers for an entry written at `now`. Returns `None` if the
+    /// entry never expires.
+    pub(crate) fn start(expiry: Expiry, now: Instant) -> Option<Self> {
+        let live_until = expiry.time_to_live.and_then(|ttl| now.checked_add(ttl));
+        let idle = expiry.time_to_idle;
+        let at = earliest(live_until, idle.and_then(|tti| now.checked_add(tti)))?;
+        Some(Self {
+            at,
+            live_until,
+            idle,
+        })
+    }
+
+    /// Records an access at `now`, pushing back the idle deadline.
+    pub(crate) fn touch(&mut self, now: Instant) {
+        if let Some(idle) = self.idle {
+            if let Some(at) = earliest(self.live_until, now.checked_add(idle)) {
+                self.at = at;
+            }
+        }
+    }
+
+    pub(crate) fn has_passed(&self, now: Instant) -> bool {
+        now >= self.at
+    }
+}
+
+fn earliest(a: Option<Instant>, b: Option<Instant>) -> Option<Instant> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+/// The clock held by a cache.
+pub(crate) struct SharedClock(Arc<dyn Clock + Send + Sync>);
+
+impl SharedClock {
+    pub(crate) fn system() -> Self {
+        Self(Arc::new(SystemClock))
+    }
+
+    pub(crate) fn new(clock: impl Clock + Send + Sync + 'static) -> Self {
+        Self(Arc::new(clock))
+    }
+
+    pub(crate) fn now(&self) -> Instant {
+        self.0.now()
+    }
+}
+
+impl Clone for SharedClock {
+    fn clone(&self) -> Self {
+        Self(Arc::clone(&self.0))
+    }
+}
+
+impl fmt::Debug for SharedClock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Clock")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deadline_takes_earliest_limit() {
+        let clock = ManualClock::new();
+        let start = clock.now();
+        let expiry = Expiry::NEVER
+            .with_time_to_live(Duration::from_secs(10))
+            .with_time_to_idle(Duration::from_secs(4));
+        let mut deadline = Deadline::start(expiry, start).unwrap();
+
+        clock.advance(Duration::from_secs(3));
+        deadline.touch(clock.now());
+        clock.advance(Duration::from_secs(3));
+        assert!(!deadline.has_passed(clock.now()));
+        deadline.touch(clock.now());
+        clock.advance(Duration::from_secs(3));
+        deadline.touch(clock.now());
+        // Idle never ran out, but the entry was written 10s ago.
+        clock.advance(Duration::from_secs(1));
+        assert!(deadline.has_passed(clock.now()));
+    }
+
+    #[test]
+    fn test_never_has_no_deadline() {
+        assert!(Deadline::start(Expiry::NEVER, Instant::now()).is_none());
+        assert!(Deadline::start(
+            Expiry::NEVER.with_time_to_live(Duration::MAX),
+            Instant::now()
+        )
+        .is_none());
+    }
+}
@@ -0,0 +1,105 @@
+//! Queues of recently evicted keys, remembered by hash only.
+
+use hashbrown::HashTable;
+
+use crate::list::{Links, List};
+
+/// An ordered set of key hashes with constant-time lookup and removal.
+///
+/// Policies use it to remember keys that have already left the cache, so
+/// that a quick return can be told apart from a first visit. Only the
+/// 64-bit hash is kept; two keys with the same hash are treated as one.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct GhostQueue {
+    hashes: Vec<u64>,
+    free: Vec<usize>,
+    links: Links,
+    list: List,
+    index: HashTable<usize>,
+}
+
+impl GhostQueue {
+    pub(crate) fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    pub(crate) fn contains(&self, hash: u64) -> bool {
+        self.find(hash).is_some()
+    }
+
+    /// Forgets `hash`, returning whether it was present.
+    pub(crate) fn remove(&mut self, hash: u64) -> bool {
+        let hashes = &self.hashes;
+        let Ok(entry) = self.index.find_entry(hash, |&i| hashes[i] == hash) else {
+            return false;
+        };
+        let (id, _) = entry.remove();
+        self.links.unlink(&mut self.list, id);
+        self.free.push(id);
+        true
+    }
+
+    /// Adds `hash` at the front, or moves it there if already present.
+    pub(crate) fn push_front(&mut self, hash: u64) {
+        if let Some(id) = self.find(hash) {
+            self.links.move_to_front(&mut self.list, id);
+            return;
+        }
+        let id = match self.free.pop() {
+            Some(id) => {
+                self.hashes[id] = hash;
+                id
+            }
+            None => {
+                self.hashes.push(hash);
+                self.hashes.len() - 1
+            }
+        };
+        let hashes = &self.hashes;
+        self.index.insert_unique(hash, id, |&i| hashes[i]);
+        self.links.push_front(&mut self.list, id);
+    }
+
+    /// Forgets and returns the hash at the back.
+    pub(crate) fn pop_back(&mut self) -> Option<u64> {
+        let hash = self.hashes[self.list.tail()?];
+        self.remove(hash);
+        Some(hash)
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.hashes.clear();
+        self.free.clear();
+        self.links.clear();
+        self.list = List::default();
+        self.index.clear();
+    }
+
+    fn find(&self, hash: u64) -> Option<usize> {
+        let hashes = &self.hashes;
+        self.index.find(hash, |&i| hashes[i] == hash).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_queue_order_and_membership() {
+        let mut ghosts = GhostQueue::default();
+        for hash in [10, 20, 30] {
+            ghosts.push_front(hash);
+        }
+        ghosts.push_front(10);
+        assert_eq!(ghosts.len(), 3);
+        assert!(ghosts.remove(30));
+        assert!(!ghosts.remove(30));
+        assert_eq!(ghosts.pop_back(), Some(20));
+        assert!(ghosts.contains(10));
+        ghosts.push_front(40);
+        assert_eq!(ghosts.pop_back(), Some(10));
+        assert_eq!(ghosts.pop_back(), Some(40));
+        assert_eq!(ghosts.pop_back(), None);
+    }
+}
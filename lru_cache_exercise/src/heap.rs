@@ -0,0 +1,184 @@
+//! An indexed binary min-heap of cache slot numbers.
+
+use std::cmp::Ordering;
+
+/// A priority queue of slots that can find, reprioritise and remove any
+/// slot in logarithmic time.
+///
+/// Each slot has a floating-point priority; the slot with the lowest comes
+/// out on top. Equal priorities are ordered by when they were last set,
+/// oldest first.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct IndexedHeap {
+    /// The slots in heap order.
+    heap: Vec<usize>,
+    /// The priority and sequence number of each queued slot, indexed by slot.
+    keys: Vec<(f64, u64)>,
+    /// The position of each queued slot in `heap`, indexed by slot.
+    positions: Vec<usize>,
+    sequence: u64,
+}
+
+impl IndexedHeap {
+    pub(crate) fn len(&self) -> usize {
+        self.heap.len()
+    }
+
+    /// Returns the slot with the lowest priority.
+    pub(crate) fn peek(&self) -> Option<usize> {
+        self.heap.first().copied()
+    }
+
+    /// Returns the slot with the lowest priority other than the top one.
+    pub(crate) fn peek_second(&self) -> Option<usize> {
+        match self.heap.len() {
+            0 | 1 => None,
+            2 => Some(self.heap[1]),
+            _ if self.less(2, 1) => Some(self.heap[2]),
+            _ => Some(self.heap[1]),
+        }
+    }
+
+    /// Returns the slot at `position` in heap order.
+    pub(crate) fn get(&self, position: usize) -> Option<usize> {
+        self.heap.get(position).copied()
+    }
+
+    /// Returns the position of a queued slot in heap order.
+    pub(crate) fn position(&self, slot: usize) -> usize {
+        self.positions[slot]
+    }
+
+    pub(crate) fn priority(&self, slot: usize) -> f64 {
+        self.keys[slot].0
+    }
+
+    /// Queues `slot`, which must not already be queued.
+    pub(crate) fn push(&mut self, slot: usize, priority: f64) {
+        if slot >= self.keys.len() {
+            self.keys.resize(slot + 1, (0.0, 0));
+            self.positions.resize(slot + 1, 0);
+        }
+        self.keys[slot] = (priority, self.next_sequence());
+        self.positions[slot] = self.heap.len();
+        self.heap.push(slot);
+        self.sift_up(self.heap.len() - 1);
+    }
+
+    /// Changes the priority of a queued slot.
+    pub(crate) fn update(&mut self, slot: usize, priority: f64) {
+        self.keys[slot] = (priority, self.next_sequence());
+        let position = self.positions[slot];
+        self.sift_up(position);
+        self.sift_down(self.positions[slot]);
+    }
+
+    /// Dequeues a queued slot.
+    pub(crate) fn remove(&mut self, slot: usize) {
+        let position = self.positions[slot];
+        let last = self.heap.len() - 1;
+        self.swap(position, last);
+        self.heap.pop();
+        if position < self.heap.len() {
+            self.sift_up(position);
+            self.sift_down(self.positions[self.heap[position]]);
+        }
+    }
+
+    /// Moves the queued slot `from` to the unqueued slot `to`.
+    pub(crate) fn relocate(&mut self, from: usize, to: usize) {
+        if to >= self.keys.len() {
+            self.keys.resize(to + 1, (0.0, 0));
+            self.positions.resize(to + 1, 0);
+        }
+        let position = self.positions[from];
+        self.keys[to] = self.keys[from];
+        self.positions[to] = position;
+        self.heap[position] = to;
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.heap.clear();
+        self.keys.clear();
+        self.positions.clear();
+    }
+
+    /// Forgets slots numbered `slots` and above, none of which may be queued.
+    pub(crate) fn truncate(&mut self, slots: usize) {
+        self.keys.truncate(slots);
+        self.keys.shrink_to_fit();
+        self.positions.truncate(slots);
+        self.positions.shrink_to_fit();
+        self.heap.shrink_to_fit();
+    }
+
+    fn next_sequence(&mut self) -> u64 {
+        self.sequence += 1;
+        self.sequence
+    }
+
+    fn less(&self, a: usize, b: usize) -> bool {
+        let (a, b) = (self.keys[self.heap[a]], self.keys[self.heap[b]]);
+        a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)) == Ordering::Less
+    }
+
+    fn swap(&mut self, a: usize, b: usize) {
+        self.heap.swap(a, b);
+        self.positions[self.heap[a]] = a;
+        self.positions[self.heap[b]] = b;
+    }
+
+    fn sift_up(&mut self, mut position: usize) {
+        while position > 0 {
+            let parent = (position - 1) / 2;
+            if !self.less(position, parent) {
+                break;
+            }
+            self.swap(position, parent);
+            position = parent;
+        }
+    }
+
+    fn sift_down(&mut self, mut position: usize) {
+        loop {
+            let left = 2 * position + 1;
+            let right = left + 1;
+            let mut smallest = position;
+            if left < self.heap.len() && self.less(left, smallest) {
+                smallest = left;
+            }
+            if right < self.heap.len() && self.less(right, smallest) {
+                smallest = right;
+            }
+            if smallest == position {
+                break;
+            }
+            self.swap(position, smallest);
+            position = smallest;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pops_in_priority_order() {
+        let mut heap = IndexedHeap::default();
+        for (slot, priority) in [3.0, 1.0, 4.0, 1.0, 5.0, 9.0, 2.0].into_iter().enumerate() {
+            heap.push(slot, priority);
+        }
+        heap.update(5, 0.5);
+        heap.remove(2);
+        heap.relocate(4, 7);
+        assert_eq!(heap.peek_second(), Some(1));
+        let mut order = Vec::new();
+        while let Some(slot) = heap.peek() {
+            order.push(slot);
+            heap.remove(slot);
+        }
+        // Slots 1 and 3 tie, and 1 was queued first.
+        assert_eq!(order, [5, 1, 3, 6, 0, 7]);
+    }
+}
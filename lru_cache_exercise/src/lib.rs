@@ -1,132 +1,69 @@
-#![allow(dead_code)]
-use std::collections::HashMap;
-use std::rc::Rc;
-use std::cell::RefCell;
-
-type Link<K, V> = Option<Rc<RefCell<Node<K, V>>>>;
-
-#[derive(Debug, Clone)]
-struct Node<K, V> {
-    key: K,
-    data: V,
-    next: Link<K, V>,
-    prev: Link<K, V>,
-}
-
-#[derive(Debug, Clone)]
-struct LruCache<K: std::hash::Hash + std::cmp::Eq, V> {
-    capacity: usize,
-    map: HashMap<K, Link<K, V>>,
-    head: Link<K, V>,
-    tail: Link<K, V>,
-}
-
-impl<K: std::hash::Hash + std::cmp::Eq + Clone, V: Clone> LruCache<K, V> {
-    pub fn new(capacity: usize) -> Self {
-        Self {
-            capacity,
-            map: HashMap::new(),
-            head: None,
-            tail: None,
-        }
-    }
-
-    pub fn get(&mut self, key: K) -> Option<V> {
-        if let Some(node) = self.map.get(&key) {
-            let node = node.clone().unwrap();
-            let data = node.borrow().data.clone();
-            self.remove_node(Some(node.clone()));
-            self.push_front(Some(node.clone()));
-            Some(data)
-        } else {
-            None
-        }
-    }
-
-    pub fn put(&mut self, key: K, value: V) {
-        if let Some(node) = self.map.get(&key) {
-            let node = node.clone().unwrap();
-            node.borrow_mut().data = value;
-            self.remove_node(Some(node.clone()));
-            self.push_front(Some(node.clone()));
-            return;
-        }
-
-        if self.map.len() >= self.capacity {
-            if let Some(tail) = self.tail.clone() {
-                let tail_key = tail.borrow().key.clone();
-                self.map.remove(&tail_key);
-                self.remove_node(Some(tail.clone()));
-            }
-        };
-
-        let new_node = Rc::new(RefCell::new(Node {
-            key: key.clone(),
-            data: value,
-            next: None,
-            prev: None,
-        }));
-
-        self.push_front(Some(new_node.clone()));
-        self.map.insert(key, Some(new_node.clone()));
-        
-    }
-
-    fn remove_node(&mut self, node: Link<K, V>) {
-        if let Some(node) = node {
-            let prev = node.borrow().prev.clone();
-            let next = node.borrow().next.clone();
-
-            if let Some(prev) = &prev {
-                prev.borrow_mut().next = next.clone();
-            } else {
-                self.head = next.clone();
-            }
-
-            if let Some(next) = &next {
-                next.borrow_mut().prev = prev.clone();
-            } else {
-                self.tail = prev.clone();
-            }
-            
-            drop(next);
-            drop(prev);
-        }
-    }
-
-    fn push_front(&mut self, node: Link<K, V>) {
-        if let Some(node) = &node {
-            node.borrow_mut().next = self.head.clone();
-            node.borrow_mut().prev = None;
-
-            if let Some(head) = &self.head {
-                head.borrow_mut().prev = Some(node.clone());
-            }
-
-            self.head = Some(node.clone());
-
-            if self.tail.is_none() {
-                self.tail = Some(node.clone());
-            }
-        }
-    }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn test_lru_cache() {
-        let mut cache = LruCache::new(2);
-        cache.put(1, 1);
-        cache.put(2, 2);
-        assert_eq!(cache.get(1), Some(1));
-        cache.put(3, 3);
-        assert_eq!(cache.get(2), None);
-        cache.put(4, 4);
-        assert_eq!(cache.get(1), None);
-        assert_eq!(cache.get(3), Some(3));
-        assert_eq!(cache.get(4), Some(4));
-    }
-}
+//! Cache data structures.
+//!
+//! [`Cache`] is a fixed-capacity map that evicts an entry chosen by its
+//! [`Policy`] when a new key would exceed its capacity. [`LruCache`] evicts
+//! the least recently used entry, [`LfuCache`] the least frequently used
+//! one, and [`FifoCache`] and [`RandomCache`] the oldest or a random one.
+//! [`ArcCache`] adapts between recency and frequency and resists scans, as
+//! do [`TwoQueueCache`] and [`SegmentedLruCache`], which keep keys seen only
+//! once away from those that are reused. [`TinyLfuCache`] admits keys
+//! into its main region only when a [`CountMinSketch`] rates them as more
+//! popular than what they would replace. [`ClockCache`] and
+//! [`ClockProCache`] approximate LRU with reference bits, so that a hit
+//! costs no list surgery; CLOCK-Pro also resists scans. [`S3FifoCache`]
+//! filters out keys used once with three FIFO queues, and [`LirsCache`]
+//! keeps hitting under loops larger than the cache. [`LruKCache`] ranks
+//! entries by their K-th most recent use, and [`GdsfCache`] weighs each
+//! entry's recomputation cost against its size.
+//! [`ConcurrentLruCache`] shards an LRU cache behind per-shard locks for use
+//! from many threads, and [`BufferedLruCache`] serves reads under a shared
+//! lock for read-heavy workloads. [`LoadingLruCache`] fills its own misses
+//! through a [`CacheLoader`], and [`AsyncLruCache`] does the same for async
+//! code while coalescing concurrent loads of one key. [`WriteBackCache`]
+//! buffers writes in front of a [`BackingStore`].
+//!
+//! ```
+//! use lru_cache_exercise::LruCache;
+//!
+//! let mut cache = LruCache::new(2);
+//! cache.put("a".to_string(), 1);
+//! cache.put("b".to_string(), 2);
+//! assert_eq!(cache.get("a"), Some(&1));
+//! cache.put("c".to_string(), 3);
+//! assert!(!cache.contains_key("b"));
+//! ```
+
+mod async_cache;
+mod buffered;
+pub mod cache;
+mod concurrent;
+mod expiry;
+mod ghost;
+mod heap;
+mod list;
+mod listener;
+mod loading;
+pub mod policy;
+mod sketch;
+mod stats;
+mod weigher;
+mod write_back;
+
+pub use async_cache::AsyncLruCache;
+pub use buffered::BufferedLruCache;
+pub use concurrent::ConcurrentLruCache;
+pub use expiry::{Clock, Expiry, ManualClock, SystemClock};
+pub use listener::{RemovalCause, RemovalListener};
+pub use loading::{CacheLoader, LoadingLruCache};
+pub use sketch::{CountMinSketch, Doorkeeper};
+pub use stats::CacheStats;
+pub use weigher::Weigher;
+pub use write_back::{BackingStore, FileStore, MemoryStore, WriteBackCache};
+
+pub use cache::{Cache, Displaced, Entry, OccupiedEntry, VacantEntry};
+pub use policy::{
+    Adaptive, ArcCache, ClockCache, ClockPro, ClockProCache, Fifo, FifoCache, Gdsf, GdsfCache, Lfu,
+    LfuCache, Lirs, LirsCache, Lru, LruCache, LruK, LruKCache, Policy, Random, RandomCache, S3Fifo,
+    S3FifoCache, SecondChance, Segmented, SegmentedLruCache, TinyLfu, TinyLfuCache, TwoQueue,
+    TwoQueueCache,
+};
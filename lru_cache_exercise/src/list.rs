@@ -0,0 +1,188 @@
+//! Doubly linked lists threaded through cache slot numbers.
+//!
+//! Policies keep their ordering here instead of in the cache's nodes. One
+//! [`Links`] table holds the neighbours of every slot, and any number of
+//! [`List`] heads can share it as long as each slot is in at most one list.
+
+/// The previous and next slot of every linked slot, indexed by slot.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Links {
+    links: Vec<Link>,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Link {
+    prev: Option<usize>,
+    next: Option<usize>,
+}
+
+/// The ends of one list whose links live in a [`Links`] table.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct List {
+    head: Option<usize>,
+    tail: Option<usize>,
+}
+
+impl List {
+    pub(crate) fn head(&self) -> Option<usize> {
+        self.head
+    }
+
+    pub(crate) fn tail(&self) -> Option<usize> {
+        self.tail
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.head.is_none()
+    }
+}
+
+impl Links {
+    /// Returns the slot after `slot` in its list.
+    pub(crate) fn next(&self, slot: usize) -> Option<usize> {
+        self.links[slot].next
+    }
+
+    /// Returns the slot before `slot` in its list.
+    pub(crate) fn prev(&self, slot: usize) -> Option<usize> {
+        self.links[slot].prev
+    }
+
+    /// Links `slot`, which must not be in any list, at the front of `list`.
+    pub(crate) fn push_front(&mut self, list: &mut List, slot: usize) {
+        self.reserve(slot);
+        self.links[slot] = Link {
+            prev: None,
+            next: list.head,
+        };
+        match list.head {
+            Some(head) => self.links[head].prev = Some(slot),
+            None => list.tail = Some(slot),
+        }
+        list.head = Some(slot);
+    }
+
+    /// Links `slot`, which must not be in any list, at the back of `list`.
+    pub(crate) fn push_back(&mut self, list: &mut List, slot: usize) {
+        match list.tail {
+            Some(tail) => self.insert_after(list, tail, slot),
+            None => self.push_front(list, slot),
+        }
+    }
+
+    /// Links `slot`, which must not be in any list, right after `anchor` in
+    /// `list`.
+    pub(crate) fn insert_after(&mut self, list: &mut List, anchor: usize, slot: usize) {
+        self.reserve(slot);
+        let next = self.links[anchor].next;
+        self.links[slot] = Link {
+            prev: Some(anchor),
+            next,
+        };
+        self.links[anchor].next = Some(slot);
+        match next {
+            Some(next) => self.links[next].prev = Some(slot),
+            None => list.tail = Some(slot),
+        }
+    }
+
+    /// Unlinks `slot` from `list`, which must contain it.
+    pub(crate) fn unlink(&mut self, list: &mut List, slot: usize) {
+        let Link { prev, next } = std::mem::take(&mut self.links[slot]);
+        match prev {
+            Some(prev) => self.links[prev].next = next,
+            None => list.head = next,
+        }
+        match next {
+            Some(next) => self.links[next].prev = prev,
+            None => list.tail = prev,
+        }
+    }
+
+    /// Moves `slot`, which must be in `list`, to its front.
+    pub(crate) fn move_to_front(&mut self, list: &mut List, slot: usize) {
+        if list.head != Some(slot) {
+            self.unlink(list, slot);
+            self.push_front(list, slot);
+        }
+    }
+
+    /// Moves the links of `from`, which is in `list`, to the unlinked slot
+    /// `to`, so that `to` takes its place.
+    pub(crate) fn relocate(&mut self, list: &mut List, from: usize, to: usize) {
+        self.reserve(to);
+        let link = std::mem::take(&mut self.links[from]);
+        self.links[to] = link;
+        match link.prev {
+            Some(prev) => self.links[prev].next = Some(to),
+            None => list.head = Some(to),
+        }
+        match link.next {
+            Some(next) => self.links[next].prev = Some(to),
+            None => list.tail = Some(to),
+        }
+    }
+
+    /// Unlinks every slot, keeping the allocated memory.
+    pub(crate) fn clear(&mut self) {
+        self.links.clear();
+    }
+
+    /// Drops the links of slots at or above `len`, which must all be
+    /// unlinked.
+    pub(crate) fn truncate(&mut self, len: usize) {
+        self.links.truncate(len);
+        self.links.shrink_to_fit();
+    }
+
+    fn reserve(&mut self, slot: usize) {
+        if slot >= self.links.len() {
+            self.links.resize(slot + 1, Link::default());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn collect(links: &Links, list: &List) -> Vec<usize> {
+        let mut slots = Vec::new();
+        let mut cursor = list.head();
+        while let Some(slot) = cursor {
+            slots.push(slot);
+            cursor = links.next(slot);
+        }
+        slots
+    }
+
+    #[test]
+    fn test_lists_share_one_table() {
+        let mut links = Links::default();
+        let (mut a, mut b) = (List::default(), List::default());
+        links.push_front(&mut a, 0);
+        links.push_front(&mut a, 3);
+        links.push_front(&mut b, 1);
+        links.push_front(&mut b, 2);
+        links.move_to_front(&mut b, 1);
+        assert_eq!(collect(&links, &a), [3, 0]);
+        assert_eq!(collect(&links, &b), [1, 2]);
+
+        links.unlink(&mut a, 0);
+        links.push_front(&mut b, 0);
+        assert_eq!(collect(&links, &b), [0, 1, 2]);
+        assert_eq!(links.prev(2), Some(1));
+
+        links.relocate(&mut b, 2, 4);
+        assert_eq!(collect(&links, &b), [0, 1, 4]);
+        assert_eq!(b.tail(), Some(4));
+
+        links.insert_after(&mut b, 1, 5);
+        links.push_back(&mut b, 6);
+        assert_eq!(collect(&links, &b), [0, 1, 5, 4, 6]);
+
+        links.unlink(&mut a, 3);
+        assert!(a.is_empty());
+        assert_eq!((a.head(), a.tail()), (None, None));
+    }
+}
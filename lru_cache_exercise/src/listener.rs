@@ -0,0 +1,85 @@
+//! Notifications for entries leaving a cache.
+
+use std::fmt;
+use std::sync::Arc;
+
+/// Why an entry left a cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RemovalCause {
+    /// Evicted to keep the cache within its capacity.
+    Capacity,
+    /// Its value was overwritten by a new value for the same key.
+    Replaced,
+    /// Removed by the caller, for example through `remove` or `pop_lru`.
+    Explicit,
+    /// Its time-to-live or time-to-idle ran out.
+    Expired,
+    /// Dropped by `clear`.
+    Cleared,
+}
+
+impl RemovalCause {
+    /// Returns `true` if the cache removed the entry on its own, rather than
+    /// because the caller replaced, removed or cleared it.
+    pub fn was_evicted(self) -> bool {
+        matches!(self, RemovalCause::Capacity | RemovalCause::Expired)
+    }
+}
+
+/// Receives every entry that leaves a cache, together with the reason.
+///
+/// The listener runs synchronously, before the value is dropped or handed
+/// back to the caller, so it can write the value back to storage or release
+/// resources tied to it. Closures taking `(&K, &V, RemovalCause)` implement
+/// this trait.
+///
+/// Entries still in the cache when it is dropped or consumed by
+/// `into_iter` are not reported.
+pub trait RemovalListener<K, V> {
+    /// Called once for each entry that leaves the cache.
+    fn on_removal(&self, key: &K, value: &V, cause: RemovalCause);
+}
+
+impl<K, V, F> RemovalListener<K, V> for F
+where
+    F: Fn(&K, &V, RemovalCause),
+{
+    fn on_removal(&self, key: &K, value: &V, cause: RemovalCause) {
+        self(key, value, cause)
+    }
+}
+
+/// An optional, shareable listener slot held by a cache.
+pub(crate) struct Listener<K, V>(Option<Arc<dyn RemovalListener<K, V> + Send + Sync>>);
+
+impl<K, V> Listener<K, V> {
+    pub(crate) fn none() -> Self {
+        Self(None)
+    }
+
+    pub(crate) fn new(listener: impl RemovalListener<K, V> + Send + Sync + 'static) -> Self {
+        Self(Some(Arc::new(listener)))
+    }
+
+    pub(crate) fn is_set(&self) -> bool {
+        self.0.is_some()
+    }
+
+    pub(crate) fn notify(&self, key: &K, value: &V, cause: RemovalCause) {
+        if let Some(listener) = &self.0 {
+            listener.on_removal(key, value, cause);
+        }
+    }
+}
+
+impl<K, V> Clone for Listener<K, V> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<K, V> fmt::Debug for Listener<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(if self.is_set() { "Some(..)" } else { "None" })
+    }
+}
@@ -0,0 +1,395 @@
+//! A read-through cache that fills its own misses.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+use std::time::Duration;
+
+use crate::cache::Displaced;
+use crate::policy::LruCache;
+use crate::stats::CacheStats;
+
+/// Computes values for keys missing from a [`LoadingLruCache`].
+///
+/// Closures taking `&K` and returning `Result<V, E>` implement this trait,
+/// with `E` as the error type.
+pub trait CacheLoader<K, V> {
+    /// The error returned when a value cannot be loaded.
+    type Error;
+
+    /// Loads the value for `key`.
+    fn load(&self, key: &K) -> Result<V, Self::Error>;
+
+    /// Loads values for several keys at once, returning them in the same
+    /// order as `keys`. The keys are distinct.
+    ///
+    /// The default calls [`load`](Self::load) for each key and stops at the
+    /// first error. Override it when the backend can answer a batch in one
+    /// round trip.
+    fn load_all(&self, keys: &[K]) -> Result<Vec<V>, Self::Error> {
+        keys.iter().map(|key| self.load(key)).collect()
+    }
+}
+
+impl<K, V, E, F> CacheLoader<K, V> for F
+where
+    F: Fn(&K) -> Result<V, E>,
+{
+    type Error = E;
+
+    fn load(&self, key: &K) -> Result<V, E> {
+        self(key)
+    }
+}
+
+/// An [`LruCache`] that loads missing values through a [`CacheLoader`].
+///
+/// [`get`](Self::get) returns the cached value or, on a miss, asks the
+/// loader, stores what it returns and hands it back. Loader errors are
+/// passed to the caller and are not cached, so the next `get` for the same
+/// key tries again, unless [negative caching](Self::with_negative_caching)
+/// is enabled. A loaded value too heavy for the cache's capacity is handed
+/// back without being cached.
+///
+/// ```
+/// use lru_cache_exercise::LoadingLruCache;
+///
+/// let mut lengths = LoadingLruCache::new(100, |word: &String| {
+///     if word.is_empty() {
+///         Err("empty word")
+///     } else {
+///         Ok(word.len())
+///     }
+/// });
+/// assert_eq!(lengths.get(&"cache".to_string()), Ok(&5));
+/// assert_eq!(lengths.get(&String::new()), Err("empty word"));
+/// assert_eq!(lengths.len(), 1);
+/// ```
+pub struct LoadingLruCache<K, V, L: CacheLoader<K, V>> {
+    cache: LruCache<K, V>,
+    loader: L,
+    negative: Option<NegativeCache<K, L::Error>>,
+    /// The last loaded value that was too heavy to cache, kept only so that
+    /// [`get`](Self::get) can lend it out.
+    uncached: Option<V>,
+}
+
+/// Recent load failures, kept for a limited time.
+struct NegativeCache<K, E> {
+    errors: LruCache<K, E>,
+    /// Captured where `E: Clone` is known, so that the rest of the cache
+    /// does not need the bound.
+    clone_error: fn(&E) -> E,
+}
+
+impl<K, V, L: CacheLoader<K, V>> LoadingLruCache<K, V, L> {
+    /// Creates an empty cache that holds at most `capacity` entries and
+    /// fills misses with `loader`.
+    pub fn new(capacity: usize, loader: L) -> Self {
+        Self::from_cache(LruCache::new(capacity), loader)
+    }
+
+    /// Wraps an existing cache, keeping its entries and its weigher,
+    /// listener and expiry settings, and fills misses with `loader`.
+    pub fn from_cache(cache: LruCache<K, V>, loader: L) -> Self {
+        Self {
+            cache,
+            loader,
+            negative: None,
+            uncached: None,
+        }
+    }
+
+    /// Remembers failed loads for `ttl`, so that [`get`](Self::get) returns
+    /// the same error again instead of calling the loader. At most `capacity`
+    /// failures are remembered. The failures use the cache's clock, so set
+    /// any custom clock on the cache before calling this.
+    ///
+    /// Bulk loads through [`get_all`](Self::get_all) are not remembered when
+    /// they fail, since the error does not say which key caused it.
+    pub fn with_negative_caching(mut self, capacity: usize, ttl: Duration) -> Self
+    where
+        L::Error: Clone,
+    {
+        let errors = LruCache::new(capacity)
+            .with_shared_clock(self.cache.shared_clock())
+            .with_time_to_live(ttl);
+        self.negative = Some(NegativeCache {
+            errors,
+            clone_error: L::Error::clone,
+        });
+        self
+    }
+
+    /// Returns the number of cached values.
+    pub fn len(&self) -> usize {
+        self.cache.len()
+    }
+
+    /// Returns `true` if no values are cached.
+    pub fn is_empty(&self) -> bool {
+        self.cache.is_empty()
+    }
+
+    /// Returns the hit, miss and eviction counts of the underlying cache.
+    /// Every miss is followed by a load, unless a failure was remembered.
+    pub fn stats(&self) -> CacheStats {
+        self.cache.stats()
+    }
+
+    /// Returns the loader.
+    pub fn loader(&self) -> &L {
+        &self.loader
+    }
+
+    /// Returns the underlying cache, for lookups that must not load.
+    pub fn cache(&self) -> &LruCache<K, V> {
+        &self.cache
+    }
+
+    /// Returns the underlying cache mutably.
+    pub fn cache_mut(&mut self) -> &mut LruCache<K, V> {
+        &mut self.cache
+    }
+
+    /// Removes every cached value and remembered failure.
+    pub fn invalidate_all(&mut self) {
+        self.cache.clear();
+        self.uncached = None;
+        if let Some(negative) = &mut self.negative {
+            negative.errors.clear();
+        }
+    }
+}
+
+impl<K: Hash + Eq + Clone, V, L: CacheLoader<K, V>> LoadingLruCache<K, V, L> {
+    /// Returns the value for `key`, loading and caching it on a miss. A
+    /// loaded value that weighs more than the cache's capacity, such as any
+    /// value in a zero-capacity cache, is returned without being cached.
+    pub fn get(&mut self, key: &K) -> Result<&V, L::Error> {
+        if let Some(index) = self.cache.lookup(key) {
+            return Ok(self.cache.value_at(index));
+        }
+        if let Some(error) = self.remembered_error(key) {
+            return Err(error);
+        }
+        match self.loader.load(key) {
+            Ok(value) => match self.cache.entry(key.clone()).or_try_insert(value) {
+                Ok(value) => Ok(value),
+                Err((_, value)) => Ok(self.uncached.insert(value)),
+            },
+            Err(error) => {
+                if let Some(negative) = &mut self.negative {
+                    let copy = (negative.clone_error)(&error);
+                    negative.errors.put(key.clone(), copy);
+                }
+                Err(error)
+            }
+        }
+    }
+
+    /// Returns clones of the values for `keys`, in order, loading every
+    /// miss in a single [`load_all`](CacheLoader::load_all) call.
+    ///
+    /// Repeated keys are loaded once. If any key has a remembered failure,
+    /// or the bulk load fails, the error is returned and nothing is loaded or
+    /// cached for the remaining keys.
+    pub fn get_all<'k>(&mut self, keys: impl IntoIterator<Item = &'k K>) -> Result<Vec<V>, L::Error>
+    where
+        K: 'k,
+        V: Clone,
+    {
+        let keys: Vec<&K> = keys.into_iter().collect();
+        let mut values: Vec<Option<V>> = Vec::with_capacity(keys.len());
+        let mut missing: Vec<K> = Vec::new();
+        let mut positions: HashMap<&K, usize> = HashMap::new();
+        for &key in &keys {
+            if let Some(value) = self.cache.get(key) {
+                values.push(Some(value.clone()));
+                continue;
+            }
+            if let Some(error) = self.remembered_error(key) {
+                return Err(error);
+            }
+            positions.entry(key).or_insert_with(|| {
+                missing.push(key.clone());
+                missing.len() - 1
+            });
+            values.push(None);
+        }
+
+        if !missing.is_empty() {
+            let loaded = self.loader.load_all(&missing)?;
+            assert_eq!(
+                loaded.len(),
+                missing.len(),
+                "load_all returned {} values for {} keys",
+                loaded.len(),
+                missing.len()
+            );
+            for (value, key) in values.iter_mut().zip(&keys) {
+                if value.is_none() {
+                    *value = Some(loaded[positions[key]].clone());
+                }
+            }
+            for (key, value) in missing.into_iter().zip(loaded) {
+                self.cache.put(key, value);
+            }
+        }
+        Ok(values.into_iter().flatten().collect())
+    }
+
+    /// Stores `value` for `key` directly, without the loader, and forgets any
+    /// remembered failure for it. See [`LruCache::put`].
+    pub fn put(&mut self, key: K, value: V) -> Vec<Displaced<K, V>> {
+        if let Some(negative) = &mut self.negative {
+            negative.errors.remove(&key);
+        }
+        self.cache.put(key, value)
+    }
+
+    /// Removes `key` and any remembered failure for it, so that the next
+    /// [`get`](Self::get) loads it again. Returns the cached value, if any.
+    pub fn invalidate(&mut self, key: &K) -> Option<V> {
+        if let Some(negative) = &mut self.negative {
+            negative.errors.remove(key);
+        }
+        self.cache.remove(key)
+    }
+
+    fn remembered_error(&mut self, key: &K) -> Option<L::Error> {
+        let negative = self.negative.as_mut()?;
+        let error = negative.errors.get(key)?;
+        Some((negative.clone_error)(error))
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug, L: CacheLoader<K, V>> fmt::Debug for LoadingLruCache<K, V, L> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LoadingLruCache")
+            .field("cache", &self.cache)
+            .field("negative_caching", &self.negative.is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ManualClock;
+    use std::cell::{Cell, RefCell};
+    use std::sync::Arc;
+
+    /// Squares numbers, fails on negative ones and counts its calls.
+    #[derive(Default)]
+    struct Squares {
+        loads: Cell<usize>,
+        batches: RefCell<Vec<Vec<i64>>>,
+    }
+
+    impl CacheLoader<i64, i64> for Squares {
+        type Error = String;
+
+        fn load(&self, key: &i64) -> Result<i64, String> {
+            self.loads.set(self.loads.get() + 1);
+            if *key < 0 {
+                Err(format!("negative key {key}"))
+            } else {
+                Ok(key * key)
+            }
+        }
+
+        fn load_all(&self, keys: &[i64]) -> Result<Vec<i64>, String> {
+            self.batches.borrow_mut().push(keys.to_vec());
+            keys.iter().map(|key| self.load(key)).collect()
+        }
+    }
+
+    #[test]
+    fn test_miss_loads_once_then_hits() {
+        let mut cache = LoadingLruCache::new(4, Squares::default());
+        assert_eq!(cache.get(&3), Ok(&9));
+        assert_eq!(cache.get(&3), Ok(&9));
+        assert_eq!(cache.loader().loads.get(), 1);
+        assert_eq!(cache.stats().hits, 1);
+        assert_eq!(cache.stats().misses, 1);
+    }
+
+    #[test]
+    fn test_errors_are_not_cached_by_default() {
+        let mut cache = LoadingLruCache::new(4, Squares::default());
+        assert_eq!(cache.get(&-1), Err("negative key -1".to_string()));
+        assert_eq!(cache.get(&-1), Err("negative key -1".to_string()));
+        assert_eq!(cache.loader().loads.get(), 2);
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_negative_caching_remembers_errors_until_ttl() {
+        let clock = Arc::new(ManualClock::new());
+        let cache = LruCache::new(4).with_clock(Arc::clone(&clock));
+        let mut cache = LoadingLruCache::from_cache(cache, Squares::default())
+            .with_negative_caching(4, Duration::from_secs(10));
+
+        assert!(cache.get(&-2).is_err());
+        assert!(cache.get(&-2).is_err());
+        assert_eq!(cache.loader().loads.get(), 1);
+
+        clock.advance(Duration::from_secs(10));
+        assert!(cache.get(&-2).is_err());
+        assert_eq!(cache.loader().loads.get(), 2);
+
+        // An explicit put overrides the remembered failure.
+        cache.put(-2, 4);
+        assert_eq!(cache.get(&-2), Ok(&4));
+    }
+
+    #[test]
+    fn test_values_too_heavy_to_cache_are_returned_uncached() {
+        let mut cache = LoadingLruCache::new(0, Squares::default());
+        assert_eq!(cache.get(&3), Ok(&9));
+        assert_eq!(cache.get(&3), Ok(&9));
+        assert_eq!(cache.loader().loads.get(), 2);
+        assert!(cache.is_empty());
+
+        let heavy = LruCache::new(10).with_weigher(|_: &i64, value: &i64| *value as usize);
+        let mut cache = LoadingLruCache::from_cache(heavy, Squares::default());
+        assert_eq!(cache.get(&3), Ok(&9));
+        assert_eq!(cache.get(&4), Ok(&16));
+        assert_eq!(cache.cache().keys().collect::<Vec<_>>(), [&3]);
+        assert_eq!(cache.stats().evictions, 0);
+    }
+
+    #[test]
+    fn test_get_all_batches_misses() {
+        let mut cache = LoadingLruCache::new(8, Squares::default());
+        cache.get(&2).unwrap();
+        let values = cache.get_all(&[1, 2, 3, 1, 4]).unwrap();
+        assert_eq!(values, [1, 4, 9, 1, 16]);
+        assert_eq!(*cache.loader().batches.borrow(), [vec![1, 3, 4]]);
+        assert_eq!(cache.len(), 4);
+
+        assert_eq!(cache.get_all(&[4, 3]).unwrap(), [16, 9]);
+        assert_eq!(cache.loader().batches.borrow().len(), 1);
+    }
+
+    #[test]
+    fn test_get_all_failure_caches_nothing() {
+        let mut cache = LoadingLruCache::new(8, Squares::default());
+        assert!(cache.get_all(&[1, -1, 2]).is_err());
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_closure_loader_and_invalidate() {
+        let calls = Cell::new(0);
+        let mut cache = LoadingLruCache::new(2, |key: &u32| {
+            calls.set(calls.get() + 1);
+            Ok::<_, ()>(key.to_string())
+        });
+        assert_eq!(cache.get(&7).map(String::as_str), Ok("7"));
+        assert_eq!(cache.invalidate(&7), Some("7".to_string()));
+        assert_eq!(cache.get(&7).map(String::as_str), Ok("7"));
+        assert_eq!(calls.get(), 2);
+    }
+}
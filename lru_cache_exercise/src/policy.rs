@@ -0,0 +1,170 @@
+//! Eviction policies for [`Cache`](crate::Cache).
+
+mod adaptive;
+mod clock;
+mod clock_pro;
+mod fifo;
+mod gdsf;
+mod lfu;
+mod lirs;
+mod lru;
+mod lru_k;
+mod random;
+mod s3_fifo;
+mod segmented;
+mod tiny_lfu;
+mod two_queue;
+
+pub use adaptive::{Adaptive, ArcCache};
+pub use clock::{ClockCache, SecondChance};
+pub use clock_pro::{ClockPro, ClockProCache};
+pub use fifo::{Fifo, FifoCache};
+pub use gdsf::{Gdsf, GdsfCache};
+pub use lfu::{Lfu, LfuCache};
+pub use lirs::{Lirs, LirsCache};
+pub use lru::{Lru, LruCache};
+pub use lru_k::{LruK, LruKCache};
+pub use random::{Random, RandomCache};
+pub use s3_fifo::{S3Fifo, S3FifoCache};
+pub use segmented::{Segmented, SegmentedLruCache};
+pub use tiny_lfu::{TinyLfu, TinyLfuCache};
+pub use two_queue::{TwoQueue, TwoQueueCache};
+
+/// Decides which entry a [`Cache`](crate::Cache) evicts when it is full.
+///
+/// The cache keeps its entries in numbered slots and tells the policy about
+/// every slot as it is filled, read and emptied; the policy answers with the
+/// slot to evict next. Slot numbers stay below the largest number of entries
+/// the cache has held at once, so a policy can keep its state in vectors
+/// indexed by slot.
+///
+/// A policy also defines the order in which the cache iterates its entries,
+/// by walking from [`first`](Self::first) through [`next`](Self::next), or
+/// from [`last`](Self::last) back through [`prev`](Self::prev). Each walk
+/// must visit every tracked slot exactly once;
+/// [`Cache::iter_mut`](crate::Cache::iter_mut) panics if a walk repeats a
+/// slot rather than lend out a value twice. By convention the order runs from the entries the policy
+/// would keep longest towards its next victims.
+///
+/// Hooks that concern one key also receive the key's 64-bit hash, taken with
+/// the cache's own hasher. Policies that remember keys after eviction keep
+/// only these hashes.
+pub trait Policy {
+    /// Tells the policy the capacity of its cache. Called on creation and
+    /// whenever the cache is resized, before anything is evicted to fit.
+    ///
+    /// The policies in this crate size their queues, segments and ghost
+    /// lists as fractions of this capacity, counted in entries. With a
+    /// weigher installed the capacity is a total weight instead, so those
+    /// shares are only as meaningful as the typical entry weight is close
+    /// to one.
+    fn set_capacity(&mut self, capacity: usize) {
+        let _ = capacity;
+    }
+
+    /// Announces that a key hashing to `hash` is about to be inserted, before
+    /// the cache evicts anything to make room for it.
+    fn before_insert(&mut self, hash: u64) {
+        let _ = hash;
+    }
+
+    /// Starts tracking `slot`, which now holds a newly inserted entry whose
+    /// key hashes to `hash`.
+    fn on_insert(&mut self, slot: usize, hash: u64);
+
+    /// Records a read of the entry in `slot`, or an overwrite of its value.
+    fn on_hit(&mut self, slot: usize);
+
+    /// Stops tracking `slot`, whose entry has left the cache for any reason.
+    fn on_remove(&mut self, slot: usize);
+
+    /// Stops tracking `slot`, whose entry was just evicted to make room.
+    /// Defaults to [`on_remove`](Self::on_remove).
+    fn on_evict(&mut self, slot: usize, hash: u64) {
+        let _ = hash;
+        self.on_remove(slot);
+    }
+
+    /// Returns the slot to evict next, or `None` if no slot is tracked. The
+    /// cache then evicts that entry, calling [`on_evict`](Self::on_evict).
+    fn victim(&mut self) -> Option<usize>;
+
+    /// Like [`victim`](Self::victim), but never returns `keep`, the slot of
+    /// an entry being overwritten, which must survive with its state intact.
+    /// Returns `None` if no other slot is tracked.
+    ///
+    /// Defaults to the victim if that is not `keep`, and otherwise to the
+    /// slot nearest the end of the iteration order that is not `keep`.
+    fn victim_excluding(&mut self, keep: usize) -> Option<usize> {
+        match self.victim()? {
+            victim if victim != keep => Some(victim),
+            _ => match self.last()? {
+                last if last != keep => Some(last),
+                last => self.prev(last),
+            },
+        }
+    }
+
+    /// Returns the first slot in iteration order.
+    fn first(&self) -> Option<usize>;
+
+    /// Returns the last slot in iteration order.
+    fn last(&self) -> Option<usize>;
+
+    /// Returns the slot after `slot` in iteration order.
+    fn next(&self, slot: usize) -> Option<usize>;
+
+    /// Returns the slot before `slot` in iteration order.
+    fn prev(&self, slot: usize) -> Option<usize>;
+
+    /// Records that the entry in `from` moved to the untracked slot `to`,
+    /// keeping its place in the policy.
+    fn relocate(&mut self, from: usize, to: usize);
+
+    /// Stops tracking every slot and forgets any evicted keys.
+    fn clear(&mut self);
+
+    /// Releases memory held for slots at or above `slots`, none of which are
+    /// tracked any more.
+    fn shrink_to(&mut self, slots: usize) {
+        let _ = slots;
+    }
+}
+
+/// Reads `key`, inserting it on a miss, the way a read-through caller would.
+#[cfg(test)]
+pub(crate) fn access<P: Policy>(cache: &mut crate::Cache<u32, (), P>, key: u32) {
+    if cache.get(&key).is_none() {
+        cache.put(key, ());
+    }
+}
+
+/// Runs a read-through workload against `cache` and an [`LruCache`] of the
+/// same capacity: `rounds` passes over the hot keys `0..hot`, each followed by
+/// `fresh` keys never seen before, then a scan of `scan` more new keys.
+/// Asserts that `cache` kept every hot key while LRU lost them all, and
+/// returns `cache` for checks specific to its policy.
+#[cfg(test)]
+pub(crate) fn assert_scan_resistant<P: Policy>(
+    mut cache: crate::Cache<u32, (), P>,
+    hot: u32,
+    rounds: usize,
+    fresh: usize,
+    scan: usize,
+) -> crate::Cache<u32, (), P> {
+    let mut lru = LruCache::new(cache.capacity());
+    let mut new_keys = 1_000_000..;
+    for _ in 0..rounds {
+        for key in (0..hot).chain(new_keys.by_ref().take(fresh)) {
+            access(&mut cache, key);
+            access(&mut lru, key);
+        }
+    }
+    for key in new_keys.take(scan) {
+        access(&mut cache, key);
+        access(&mut lru, key);
+    }
+    assert!((0..hot).all(|key| cache.contains_key(&key)));
+    assert!((0..hot).all(|key| !lru.contains_key(&key)));
+    cache
+}
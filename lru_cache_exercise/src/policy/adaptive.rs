@@ -0,0 +1,312 @@
+use super::Policy;
+use crate::cache::Cache;
+use crate::ghost::GhostQueue;
+use crate::list::{Links, List};
+
+/// A [`Cache`] using Adaptive Replacement.
+pub type ArcCache<K, V> = Cache<K, V, Adaptive>;
+
+/// Adaptive Replacement Cache (ARC), after Megiddo and Modha.
+///
+/// Resident entries sit in two LRU lists: T1 for keys seen once recently,
+/// and T2 for keys seen at least twice. Evicted keys are remembered, by hash
+/// only, in the ghost lists B1 and B2 that shadow T1 and T2. A miss that hits
+/// a ghost shows which list was evicted from too eagerly, and moves the
+/// target size of T1, called `p`, towards it: B1 hits grow the target and B2
+/// hits shrink it. The victim comes from T1 while it is larger than the
+/// target, and from T2 otherwise.
+///
+/// A key read once and never again only ever enters T1, so a long scan
+/// displaces at most T1's share of the cache and leaves T2 alone.
+///
+/// Iteration runs through T2 and then T1, each from most to least recently
+/// used.
+///
+/// ```
+/// use lru_cache_exercise::ArcCache;
+///
+/// let mut cache = ArcCache::new(4);
+/// for key in 0..3 {
+///     cache.put(key, ());
+///     cache.get(&key);
+/// }
+/// for key in 100..200 {
+///     cache.put(key, ());
+/// }
+/// assert!((0..3).all(|key| cache.contains_key(&key)));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Adaptive {
+    links: Links,
+    t1: List,
+    t2: List,
+    t1_len: usize,
+    t2_len: usize,
+    /// Whether each tracked slot is in T2 rather than T1, indexed by slot.
+    in_t2: Vec<bool>,
+    b1: GhostQueue,
+    b2: GhostQueue,
+    capacity: usize,
+    target: usize,
+    /// Whether the key being inserted was found in B2, which tips the choice
+    /// of victim towards T1 when T1 is exactly at its target.
+    incoming_from_b2: bool,
+    b1_hits: u64,
+    b2_hits: u64,
+}
+
+impl Adaptive {
+    /// Creates a policy that tracks no slots.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the current target size of T1, the adaptation parameter `p`.
+    pub fn target(&self) -> usize {
+        self.target
+    }
+
+    /// Returns the number of entries in T1, seen once recently.
+    pub fn recent_len(&self) -> usize {
+        self.t1_len
+    }
+
+    /// Returns the number of entries in T2, seen at least twice.
+    pub fn frequent_len(&self) -> usize {
+        self.t2_len
+    }
+
+    /// Returns how many inserted keys were found in B1, the ghosts of T1.
+    pub fn recent_ghost_hits(&self) -> u64 {
+        self.b1_hits
+    }
+
+    /// Returns how many inserted keys were found in B2, the ghosts of T2.
+    pub fn frequent_ghost_hits(&self) -> u64 {
+        self.b2_hits
+    }
+
+    fn list(&mut self, slot: usize) -> &mut List {
+        if self.in_t2[slot] {
+            &mut self.t2
+        } else {
+            &mut self.t1
+        }
+    }
+
+    fn unlink(&mut self, slot: usize) {
+        let list = if self.in_t2[slot] {
+            self.t2_len -= 1;
+            &mut self.t2
+        } else {
+            self.t1_len -= 1;
+            &mut self.t1
+        };
+        self.links.unlink(list, slot);
+    }
+
+    fn push_front(&mut self, slot: usize, frequent: bool) {
+        self.in_t2[slot] = frequent;
+        if frequent {
+            self.t2_len += 1;
+            self.links.push_front(&mut self.t2, slot);
+        } else {
+            self.t1_len += 1;
+            self.links.push_front(&mut self.t1, slot);
+        }
+    }
+
+    /// Drops the oldest ghosts until T1 and B1 together fit the capacity and
+    /// all four lists together fit twice the capacity.
+    fn trim_ghosts(&mut self) {
+        while self.t1_len + self.b1.len() > self.capacity && self.b1.pop_back().is_some() {}
+        let resident = self.t1_len + self.t2_len;
+        while resident + self.b1.len() + self.b2.len() > 2 * self.capacity
+            && self.b2.pop_back().is_some()
+        {}
+    }
+}
+
+impl Policy for Adaptive {
+    fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.target = self.target.min(capacity);
+        self.trim_ghosts();
+    }
+
+    fn before_insert(&mut self, hash: u64) {
+        self.incoming_from_b2 = false;
+        if self.b1.contains(hash) {
+            self.b1_hits += 1;
+            let delta = (self.b2.len() / self.b1.len()).max(1);
+            self.target = (self.target + delta).min(self.capacity);
+        } else if self.b2.contains(hash) {
+            self.b2_hits += 1;
+            self.incoming_from_b2 = true;
+            let delta = (self.b1.len() / self.b2.len()).max(1);
+            self.target = self.target.saturating_sub(delta);
+        }
+    }
+
+    fn on_insert(&mut self, slot: usize, hash: u64) {
+        if slot >= self.in_t2.len() {
+            self.in_t2.resize(slot + 1, false);
+        }
+        let was_ghost = self.b1.remove(hash) | self.b2.remove(hash);
+        self.incoming_from_b2 = false;
+        self.push_front(slot, was_ghost);
+        self.trim_ghosts();
+    }
+
+    fn on_hit(&mut self, slot: usize) {
+        self.unlink(slot);
+        self.push_front(slot, true);
+    }
+
+    fn on_remove(&mut self, slot: usize) {
+        self.unlink(slot);
+    }
+
+    fn on_evict(&mut self, slot: usize, hash: u64) {
+        let frequent = self.in_t2[slot];
+        self.unlink(slot);
+        if frequent {
+            self.b2.push_front(hash);
+        } else {
+            self.b1.push_front(hash);
+        }
+        self.trim_ghosts();
+    }
+
+    fn victim(&mut self) -> Option<usize> {
+        let from_t1 = self.t1_len > 0
+            && (self.t1_len > self.target
+                || (self.incoming_from_b2 && self.t1_len == self.target)
+                || self.t2_len == 0);
+        if from_t1 {
+            self.t1.tail()
+        } else {
+            self.t2.tail()
+        }
+    }
+
+    fn first(&self) -> Option<usize> {
+        self.t2.head().or(self.t1.head())
+    }
+
+    fn last(&self) -> Option<usize> {
+        self.t1.tail().or(self.t2.tail())
+    }
+
+    fn next(&self, slot: usize) -> Option<usize> {
+        match self.links.next(slot) {
+            None if self.in_t2[slot] => self.t1.head(),
+            next => next,
+        }
+    }
+
+    fn prev(&self, slot: usize) -> Option<usize> {
+        match self.links.prev(slot) {
+            None if !self.in_t2[slot] => self.t2.tail(),
+            prev => prev,
+        }
+    }
+
+    fn relocate(&mut self, from: usize, to: usize) {
+        if to >= self.in_t2.len() {
+            self.in_t2.resize(to + 1, false);
+        }
+        self.in_t2[to] = self.in_t2[from];
+        let mut list = *self.list(from);
+        self.links.relocate(&mut list, from, to);
+        *self.list(to) = list;
+    }
+
+    fn clear(&mut self) {
+        self.links.clear();
+        self.t1 = List::default();
+        self.t2 = List::default();
+        self.t1_len = 0;
+        self.t2_len = 0;
+        self.in_t2.clear();
+        self.b1.clear();
+        self.b2.clear();
+        self.target = 0;
+    }
+
+    fn shrink_to(&mut self, slots: usize) {
+        self.links.truncate(slots);
+        self.in_t2.truncate(slots);
+        self.in_t2.shrink_to_fit();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::policy::assert_scan_resistant;
+    use crate::Displaced;
+
+    #[test]
+    fn test_ghost_hits_adapt_the_target() {
+        let mut cache = ArcCache::new(2);
+        cache.put("a", ());
+        cache.get("a");
+        cache.put("b", ());
+        assert_eq!(cache.put("c", ()), [Displaced::Evicted("b", ())]);
+
+        // "b" left T1 too early: grow T1's target.
+        assert_eq!(cache.put("b", ()), [Displaced::Evicted("a", ())]);
+        let arc = cache.policy();
+        assert_eq!((arc.target(), arc.recent_ghost_hits()), (1, 1));
+        assert_eq!((arc.recent_len(), arc.frequent_len()), (1, 1));
+        assert_eq!(cache.keys().copied().collect::<Vec<_>>(), ["b", "c"]);
+
+        // "a" left T2 too early: shrink the target again.
+        assert_eq!(cache.put("a", ()), [Displaced::Evicted("c", ())]);
+        let arc = cache.policy();
+        assert_eq!((arc.target(), arc.frequent_ghost_hits()), (0, 1));
+        assert_eq!(arc.frequent_len(), 2);
+    }
+
+    #[test]
+    fn test_scan_keeps_frequent_entries() {
+        assert_scan_resistant(ArcCache::new(8), 6, 2, 0, 1_000);
+    }
+
+    #[test]
+    fn test_ghosts_are_bounded() {
+        let mut cache = ArcCache::new(16);
+        for key in 0..10_000 {
+            cache.put(key, ());
+            if key % 3 == 0 {
+                cache.get(&(key / 2));
+            }
+        }
+        let arc = cache.policy();
+        assert!(arc.b1.len() + arc.b2.len() <= 16);
+        assert!(arc.t1_len + arc.b1.len() <= 16);
+        assert_eq!(arc.t1_len + arc.t2_len, cache.len());
+        assert!(arc.target() <= 16);
+    }
+
+    #[test]
+    fn test_iterates_frequent_then_recent() {
+        let mut cache = ArcCache::new(4);
+        for key in 1..=4 {
+            cache.put(key, ());
+        }
+        cache.get(&2);
+        cache.get(&3);
+        assert_eq!(cache.keys().copied().collect::<Vec<_>>(), [3, 2, 4, 1]);
+        assert_eq!(
+            cache.keys().rev().copied().collect::<Vec<_>>(),
+            [1, 4, 2, 3]
+        );
+        cache.remove(&4);
+        cache.remove(&1);
+        cache.shrink_to_fit();
+        assert_eq!(cache.keys().copied().collect::<Vec<_>>(), [3, 2]);
+        assert_eq!(cache.policy().frequent_len(), 2);
+    }
+}
@@ -0,0 +1,206 @@
+use super::Policy;
+use crate::cache::Cache;
+
+/// A [`Cache`] using CLOCK.
+pub type ClockCache<K, V> = Cache<K, V, SecondChance>;
+
+/// CLOCK, also known as second chance: an approximation of LRU.
+///
+/// Entries sit in a circular array, each with a reference bit that a hit
+/// sets; hits never move anything. To choose a victim, a hand sweeps the
+/// array, clearing set bits as it passes, and stops at the first entry
+/// whose bit is already clear. A new entry takes the place of the one just
+/// evicted, right behind the hand, so it is the last the hand will reach.
+///
+/// Iteration starts with the entry just behind the hand and runs backwards
+/// around the clock, ending with the entry under the hand.
+///
+/// ```
+/// use lru_cache_exercise::ClockCache;
+///
+/// let mut cache = ClockCache::new(2);
+/// cache.put("a", 1);
+/// cache.put("b", 2);
+/// cache.get("a");
+/// cache.put("c", 3);
+/// assert!(cache.contains_key("a"));
+/// assert!(!cache.contains_key("b"));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct SecondChance {
+    /// The clock face: the slot at each position, or nothing where an entry
+    /// was removed.
+    ring: Vec<Option<usize>>,
+    /// The empty positions in `ring`, most recently emptied last.
+    holes: Vec<usize>,
+    /// The position of each tracked slot in `ring`, indexed by slot.
+    positions: Vec<usize>,
+    /// The reference bit of each tracked slot, indexed by slot.
+    referenced: Vec<bool>,
+    hand: usize,
+}
+
+impl SecondChance {
+    /// Creates a policy that tracks no slots.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the ring position that iteration visits `rank`th.
+    fn position(&self, rank: usize) -> usize {
+        let len = self.ring.len();
+        (self.hand + len - 1 - rank) % len
+    }
+
+    /// Returns the iteration rank of the entry at ring `position`.
+    fn rank(&self, position: usize) -> usize {
+        let len = self.ring.len();
+        (self.hand + len - 1 - position) % len
+    }
+
+    fn find(&self, mut ranks: impl Iterator<Item = usize>) -> Option<usize> {
+        ranks.find_map(|rank| self.ring[self.position(rank)])
+    }
+}
+
+impl Policy for SecondChance {
+    fn on_insert(&mut self, slot: usize, _hash: u64) {
+        if slot >= self.positions.len() {
+            self.positions.resize(slot + 1, 0);
+            self.referenced.resize(slot + 1, false);
+        }
+        let position = match self.holes.pop() {
+            Some(position) => position,
+            None => {
+                self.ring.push(None);
+                self.ring.len() - 1
+            }
+        };
+        self.ring[position] = Some(slot);
+        self.positions[slot] = position;
+        self.referenced[slot] = false;
+    }
+
+    fn on_hit(&mut self, slot: usize) {
+        self.referenced[slot] = true;
+    }
+
+    fn on_remove(&mut self, slot: usize) {
+        let position = self.positions[slot];
+        self.ring[position] = None;
+        self.holes.push(position);
+        if self.holes.len() == self.ring.len() {
+            self.ring.clear();
+            self.holes.clear();
+            self.hand = 0;
+        }
+    }
+
+    fn victim(&mut self) -> Option<usize> {
+        if self.ring.is_empty() {
+            return None;
+        }
+        loop {
+            let position = self.hand;
+            self.hand = (self.hand + 1) % self.ring.len();
+            let Some(slot) = self.ring[position] else {
+                continue;
+            };
+            if !std::mem::take(&mut self.referenced[slot]) {
+                return Some(slot);
+            }
+        }
+    }
+
+    fn first(&self) -> Option<usize> {
+        self.find(0..self.ring.len())
+    }
+
+    fn last(&self) -> Option<usize> {
+        self.find((0..self.ring.len()).rev())
+    }
+
+    fn next(&self, slot: usize) -> Option<usize> {
+        let rank = self.rank(self.positions[slot]);
+        self.find(rank + 1..self.ring.len())
+    }
+
+    fn prev(&self, slot: usize) -> Option<usize> {
+        let rank = self.rank(self.positions[slot]);
+        self.find((0..rank).rev())
+    }
+
+    fn relocate(&mut self, from: usize, to: usize) {
+        if to >= self.positions.len() {
+            self.positions.resize(to + 1, 0);
+            self.referenced.resize(to + 1, false);
+        }
+        let position = self.positions[from];
+        self.positions[to] = position;
+        self.referenced[to] = self.referenced[from];
+        self.ring[position] = Some(to);
+    }
+
+    fn clear(&mut self) {
+        self.ring.clear();
+        self.holes.clear();
+        self.positions.clear();
+        self.referenced.clear();
+        self.hand = 0;
+    }
+
+    fn shrink_to(&mut self, slots: usize) {
+        // Close up the holes, keeping the hand on the same entry.
+        let hand = self.ring[..self.hand].iter().flatten().count();
+        self.ring.retain(Option::is_some);
+        self.ring.shrink_to_fit();
+        self.holes = Vec::new();
+        self.hand = if hand < self.ring.len() { hand } else { 0 };
+        for (position, slot) in self.ring.iter().enumerate() {
+            if let Some(slot) = *slot {
+                self.positions[slot] = position;
+            }
+        }
+        self.positions.truncate(slots);
+        self.positions.shrink_to_fit();
+        self.referenced.truncate(slots);
+        self.referenced.shrink_to_fit();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Displaced;
+
+    #[test]
+    fn test_referenced_entries_get_a_second_chance() {
+        let mut cache = ClockCache::new(3);
+        for key in 1..=3 {
+            cache.put(key, ());
+        }
+        cache.get(&1);
+        assert_eq!(cache.put(4, ()), [Displaced::Evicted(2, ())]);
+        assert_eq!(cache.put(5, ()), [Displaced::Evicted(3, ())]);
+        // The hand cleared 1's bit on its first pass.
+        assert_eq!(cache.put(6, ()), [Displaced::Evicted(1, ())]);
+    }
+
+    #[test]
+    fn test_iteration_ends_at_the_hand() {
+        let mut cache = ClockCache::new(3);
+        for key in 1..=3 {
+            cache.put(key, ());
+        }
+        cache.get(&1);
+        cache.put(4, ());
+        assert_eq!(cache.keys().copied().collect::<Vec<_>>(), [4, 1, 3]);
+        assert_eq!(cache.keys().rev().copied().collect::<Vec<_>>(), [3, 1, 4]);
+
+        cache.remove(&1);
+        cache.shrink_to_fit();
+        assert_eq!(cache.keys().copied().collect::<Vec<_>>(), [4, 3]);
+        cache.put(5, ());
+        assert_eq!(cache.put(6, ()), [Displaced::Evicted(3, ())]);
+    }
+}
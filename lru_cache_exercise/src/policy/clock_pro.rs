@@ -0,0 +1,459 @@
+use hashbrown::HashTable;
+
+use super::Policy;
+use crate::cache::Cache;
+use crate::list::{Links, List};
+
+/// A [`Cache`] using CLOCK-Pro.
+pub type ClockProCache<K, V> = Cache<K, V, ClockPro>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Status {
+    /// Resident and reused recently enough to be kept over cold pages.
+    Hot,
+    /// Resident, and on trial until the cold hand reaches it.
+    Cold,
+    /// Evicted from cold but still on trial, remembered by hash only.
+    Test,
+}
+
+/// A page on the clock: a resident entry or the ghost of an evicted one.
+#[derive(Debug, Clone)]
+struct Page {
+    hash: u64,
+    /// The cache slot of a resident page.
+    slot: Option<usize>,
+    status: Status,
+    referenced: bool,
+}
+
+/// CLOCK-Pro, after Jiang, Chen and Zhang: scan-resistant CLOCK.
+///
+/// Like [CLOCK](super::SecondChance), hits only set a reference bit. Pages
+/// are hot or cold, and a single clock also holds test pages, the ghosts of
+/// recently evicted cold pages, remembered by hash only. Three hands sweep
+/// it:
+///
+/// - The cold hand looks for the victim. A referenced cold page is promoted
+///   to hot instead; an unreferenced one is evicted and becomes a test page.
+/// - The hot hand turns unreferenced hot pages cold whenever there are more
+///   hot pages than the hot allocation allows, clearing reference bits as
+///   it goes.
+/// - The test hand drops test pages once there are more of them than the
+///   capacity.
+///
+/// A key inserted while its test page is on the clock is reused at a
+/// distance the cache could have covered, so it enters as hot and grows the
+/// cold allocation; a test page dropped without such a hit shrinks it. The
+/// cold allocation starts out at the whole capacity and never falls below
+/// one.
+///
+/// Iteration starts with the page just behind the cold hand and runs
+/// backwards around the clock, skipping test pages.
+///
+/// ```
+/// use lru_cache_exercise::ClockProCache;
+///
+/// let mut cache = ClockProCache::new(2);
+/// cache.put("a", 1);
+/// cache.put("b", 2);
+/// cache.get("a");
+/// cache.put("c", 3);
+/// assert!(cache.contains_key("a"));
+/// assert!(!cache.contains_key("b"));
+/// assert_eq!(cache.policy().test_len(), 1);
+/// ```
+#[derive(Debug, Clone)]
+pub struct ClockPro {
+    pages: Vec<Page>,
+    free: Vec<usize>,
+    /// The clock, linked by page number and read as a circle.
+    links: Links,
+    clock: List,
+    /// The page of each tracked slot, indexed by slot.
+    page_of: Vec<usize>,
+    /// The test pages, by hash.
+    tests: HashTable<usize>,
+    hot_hand: Option<usize>,
+    cold_hand: Option<usize>,
+    test_hand: Option<usize>,
+    hot_len: usize,
+    cold_len: usize,
+    capacity: usize,
+    cold_target: usize,
+    /// Whether the key being inserted hit a test page.
+    incoming_hot: bool,
+}
+
+impl ClockPro {
+    /// Creates a policy that tracks no slots.
+    pub fn new() -> Self {
+        Self {
+            pages: Vec::new(),
+            free: Vec::new(),
+            links: Links::default(),
+            clock: List::default(),
+            page_of: Vec::new(),
+            tests: HashTable::new(),
+            hot_hand: None,
+            cold_hand: None,
+            test_hand: None,
+            hot_len: 0,
+            cold_len: 0,
+            capacity: 0,
+            cold_target: usize::MAX,
+            incoming_hot: false,
+        }
+    }
+
+    /// Returns the number of hot entries.
+    pub fn hot_len(&self) -> usize {
+        self.hot_len
+    }
+
+    /// Returns the number of cold entries.
+    pub fn cold_len(&self) -> usize {
+        self.cold_len
+    }
+
+    /// Returns the number of evicted keys still remembered as test pages.
+    pub fn test_len(&self) -> usize {
+        self.tests.len()
+    }
+
+    /// Returns how many entries the cold pages are currently allowed, the
+    /// adaptation parameter.
+    pub fn cold_target(&self) -> usize {
+        self.cold_target
+    }
+
+    fn hot_target(&self) -> usize {
+        self.capacity.saturating_sub(self.cold_target)
+    }
+
+    /// Returns the page after `page` going round the clock.
+    fn clockwise(&self, page: usize) -> usize {
+        self.links
+            .next(page)
+            .or(self.clock.head())
+            .expect("the clock holds the page")
+    }
+
+    /// Returns the page before `page` going round the clock.
+    fn anticlockwise(&self, page: usize) -> usize {
+        self.links
+            .prev(page)
+            .or(self.clock.tail())
+            .expect("the clock holds the page")
+    }
+
+    /// Adds a page right behind the hot hand, where every hand reaches it
+    /// last.
+    fn add_page(&mut self, page: Page) -> usize {
+        let id = match self.free.pop() {
+            Some(id) => {
+                self.pages[id] = page;
+                id
+            }
+            None => {
+                self.pages.push(page);
+                self.pages.len() - 1
+            }
+        };
+        match self.hot_hand {
+            Some(hand) => match self.links.prev(hand) {
+                Some(prev) => self.links.insert_after(&mut self.clock, prev, id),
+                None => self.links.push_back(&mut self.clock, id),
+            },
+            None => {
+                self.links.push_back(&mut self.clock, id);
+                self.hot_hand = Some(id);
+                self.cold_hand = Some(id);
+                self.test_hand = Some(id);
+            }
+        }
+        id
+    }
+
+    /// Takes `page` off the clock, moving any hand on it to the next page.
+    fn remove_page(&mut self, page: usize) {
+        let next = self.clockwise(page);
+        let next = (next != page).then_some(next);
+        for hand in [&mut self.hot_hand, &mut self.cold_hand, &mut self.test_hand] {
+            if *hand == Some(page) {
+                *hand = next;
+            }
+        }
+        self.links.unlink(&mut self.clock, page);
+        self.free.push(page);
+    }
+
+    /// Advances the hot hand by one page.
+    fn run_hot_hand(&mut self) {
+        if self.hot_hand == self.test_hand {
+            self.run_test_hand();
+        }
+        let Some(id) = self.hot_hand else { return };
+        let page = &mut self.pages[id];
+        if page.status == Status::Hot {
+            if page.referenced {
+                page.referenced = false;
+            } else {
+                page.status = Status::Cold;
+                self.hot_len -= 1;
+                self.cold_len += 1;
+            }
+        }
+        self.hot_hand = Some(self.clockwise(id));
+    }
+
+    /// Advances the test hand by one page, dropping it if it is a test page.
+    fn run_test_hand(&mut self) {
+        let Some(id) = self.test_hand else { return };
+        if self.pages[id].status != Status::Test {
+            self.test_hand = Some(self.clockwise(id));
+            return;
+        }
+        let hash = self.pages[id].hash;
+        if let Ok(entry) = self.tests.find_entry(hash, |&test| test == id) {
+            entry.remove();
+        }
+        self.remove_page(id);
+        self.cold_target = self.cold_target.saturating_sub(1).max(1);
+    }
+
+    /// Demotes hot pages until they fit the hot allocation and at least one
+    /// page is cold.
+    fn balance(&mut self) {
+        while self.hot_len > 0 && (self.hot_len > self.hot_target() || self.cold_len == 0) {
+            self.run_hot_hand();
+        }
+    }
+}
+
+impl Default for ClockPro {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Policy for ClockPro {
+    fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.cold_target = self.cold_target.min(capacity).max(1);
+        while self.tests.len() > capacity {
+            self.run_test_hand();
+        }
+    }
+
+    fn before_insert(&mut self, hash: u64) {
+        let Ok(entry) = self
+            .tests
+            .find_entry(hash, |&test| self.pages[test].hash == hash)
+        else {
+            return;
+        };
+        let (id, _) = entry.remove();
+        self.remove_page(id);
+        self.cold_target = (self.cold_target + 1).min(self.capacity.max(1));
+        self.incoming_hot = true;
+    }
+
+    fn on_insert(&mut self, slot: usize, hash: u64) {
+        if slot >= self.page_of.len() {
+            self.page_of.resize(slot + 1, 0);
+        }
+        let status = if std::mem::take(&mut self.incoming_hot) {
+            self.hot_len += 1;
+            Status::Hot
+        } else {
+            self.cold_len += 1;
+            Status::Cold
+        };
+        self.page_of[slot] = self.add_page(Page {
+            hash,
+            slot: Some(slot),
+            status,
+            referenced: false,
+        });
+    }
+
+    fn on_hit(&mut self, slot: usize) {
+        self.pages[self.page_of[slot]].referenced = true;
+    }
+
+    fn on_remove(&mut self, slot: usize) {
+        let id = self.page_of[slot];
+        match self.pages[id].status {
+            Status::Hot => self.hot_len -= 1,
+            _ => self.cold_len -= 1,
+        }
+        self.remove_page(id);
+    }
+
+    fn on_evict(&mut self, slot: usize, hash: u64) {
+        let id = self.page_of[slot];
+        if self.pages[id].status != Status::Cold {
+            self.on_remove(slot);
+            return;
+        }
+        let page = &mut self.pages[id];
+        page.slot = None;
+        page.status = Status::Test;
+        page.referenced = false;
+        self.cold_len -= 1;
+        let pages = &self.pages;
+        self.tests.insert_unique(hash, id, |&test| pages[test].hash);
+        while self.tests.len() > self.capacity {
+            self.run_test_hand();
+        }
+    }
+
+    fn victim(&mut self) -> Option<usize> {
+        if self.hot_len + self.cold_len == 0 {
+            return None;
+        }
+        self.balance();
+        loop {
+            let id = self.cold_hand?;
+            self.cold_hand = Some(self.clockwise(id));
+            let page = &mut self.pages[id];
+            if page.status != Status::Cold {
+                continue;
+            }
+            if !page.referenced {
+                return page.slot;
+            }
+            page.referenced = false;
+            page.status = Status::Hot;
+            self.cold_len -= 1;
+            self.hot_len += 1;
+            self.balance();
+        }
+    }
+
+    fn first(&self) -> Option<usize> {
+        let stop = self.cold_hand?;
+        let mut page = stop;
+        loop {
+            page = self.anticlockwise(page);
+            if let Some(slot) = self.pages[page].slot {
+                return Some(slot);
+            }
+            if page == stop {
+                return None;
+            }
+        }
+    }
+
+    fn last(&self) -> Option<usize> {
+        let stop = self.cold_hand?;
+        let mut page = stop;
+        loop {
+            if let Some(slot) = self.pages[page].slot {
+                return Some(slot);
+            }
+            page = self.clockwise(page);
+            if page == stop {
+                return None;
+            }
+        }
+    }
+
+    fn next(&self, slot: usize) -> Option<usize> {
+        let stop = self.cold_hand?;
+        let mut page = self.page_of[slot];
+        while page != stop {
+            page = self.anticlockwise(page);
+            if let Some(slot) = self.pages[page].slot {
+                return Some(slot);
+            }
+        }
+        None
+    }
+
+    fn prev(&self, slot: usize) -> Option<usize> {
+        let stop = self.cold_hand?;
+        let mut page = self.page_of[slot];
+        loop {
+            page = self.clockwise(page);
+            if page == stop {
+                return None;
+            }
+            if let Some(slot) = self.pages[page].slot {
+                return Some(slot);
+            }
+        }
+    }
+
+    fn relocate(&mut self, from: usize, to: usize) {
+        if to >= self.page_of.len() {
+            self.page_of.resize(to + 1, 0);
+        }
+        let id = self.page_of[from];
+        self.page_of[to] = id;
+        self.pages[id].slot = Some(to);
+    }
+
+    fn clear(&mut self) {
+        *self = Self {
+            capacity: self.capacity,
+            cold_target: self.capacity.max(1),
+            ..Self::new()
+        };
+    }
+
+    fn shrink_to(&mut self, slots: usize) {
+        self.page_of.truncate(slots);
+        self.page_of.shrink_to_fit();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::policy::{access, assert_scan_resistant};
+
+    #[test]
+    fn test_scan_keeps_hot_entries() {
+        let clock_pro = assert_scan_resistant(ClockProCache::new(16), 8, 50, 4, 1_000);
+        assert_eq!(clock_pro.policy().hot_len(), 8);
+    }
+
+    #[test]
+    fn test_pages_are_bounded() {
+        let mut cache = ClockProCache::new(8);
+        for key in 0..1_000 {
+            access(&mut cache, key % 13);
+            access(&mut cache, key);
+        }
+        let clock_pro = cache.policy();
+        assert_eq!(clock_pro.hot_len() + clock_pro.cold_len(), cache.len());
+        assert!(clock_pro.test_len() <= 8);
+        assert!((1..=8).contains(&clock_pro.cold_target()));
+        // Only test pages and resident pages are on the clock.
+        let on_clock = clock_pro.pages.len() - clock_pro.free.len();
+        assert_eq!(on_clock, cache.len() + clock_pro.test_len());
+    }
+
+    #[test]
+    fn test_iteration_skips_test_pages() {
+        let mut cache = ClockProCache::new(4);
+        for key in 0..10 {
+            cache.put(key, ());
+        }
+        assert!(cache.policy().test_len() > 0);
+        let mut keys: Vec<_> = cache.keys().copied().collect();
+        let mut reversed: Vec<_> = cache.keys().rev().copied().collect();
+        reversed.reverse();
+        assert_eq!(keys, reversed);
+        keys.sort_unstable();
+        assert_eq!(keys, [6, 7, 8, 9]);
+
+        cache.remove(&7);
+        cache.shrink_to_fit();
+        let mut keys: Vec<_> = cache.keys().copied().collect();
+        keys.sort_unstable();
+        assert_eq!(keys, [6, 8, 9]);
+    }
+}
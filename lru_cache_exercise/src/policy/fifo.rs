@@ -0,0 +1,86 @@
+use super::Policy;
+use crate::cache::Cache;
+use crate::list::{Links, List};
+
+/// A [`Cache`] that evicts the oldest entry.
+pub type FifoCache<K, V> = Cache<K, V, Fifo>;
+
+/// Evicts entries in the order they were inserted, ignoring reads.
+///
+/// Overwriting a value keeps the entry's place in the queue. Iteration runs
+/// from newest to oldest.
+#[derive(Debug, Clone, Default)]
+pub struct Fifo {
+    links: Links,
+    queue: List,
+}
+
+impl Fifo {
+    /// Creates a policy that tracks no slots.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Policy for Fifo {
+    fn on_insert(&mut self, slot: usize, _hash: u64) {
+        self.links.push_front(&mut self.queue, slot);
+    }
+
+    fn on_hit(&mut self, _slot: usize) {}
+
+    fn on_remove(&mut self, slot: usize) {
+        self.links.unlink(&mut self.queue, slot);
+    }
+
+    fn victim(&mut self) -> Option<usize> {
+        self.queue.tail()
+    }
+
+    fn first(&self) -> Option<usize> {
+        self.queue.head()
+    }
+
+    fn last(&self) -> Option<usize> {
+        self.queue.tail()
+    }
+
+    fn next(&self, slot: usize) -> Option<usize> {
+        self.links.next(slot)
+    }
+
+    fn prev(&self, slot: usize) -> Option<usize> {
+        self.links.prev(slot)
+    }
+
+    fn relocate(&mut self, from: usize, to: usize) {
+        self.links.relocate(&mut self.queue, from, to);
+    }
+
+    fn clear(&mut self) {
+        self.links.clear();
+        self.queue = List::default();
+    }
+
+    fn shrink_to(&mut self, slots: usize) {
+        self.links.truncate(slots);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reads_do_not_save_the_oldest_entry() {
+        let mut cache = FifoCache::new(2);
+        cache.put(1, 1);
+        cache.put(2, 2);
+        assert_eq!(cache.get(&1), Some(&1));
+        cache.put(1, 10);
+        cache.put(3, 3);
+        assert!(!cache.contains_key(&1));
+        assert_eq!(cache.keys().copied().collect::<Vec<_>>(), [3, 2]);
+        assert_eq!(cache.stats().hits, 1);
+    }
+}
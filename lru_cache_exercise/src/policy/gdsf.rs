@@ -0,0 +1,306 @@
+use std::borrow::Borrow;
+use std::hash::Hash;
+
+use super::Policy;
+use crate::cache::{Cache, Displaced};
+use crate::heap::IndexedHeap;
+
+/// A [`Cache`] using GreedyDual-Size-Frequency.
+pub type GdsfCache<K, V> = Cache<K, V, Gdsf>;
+
+/// What GDSF knows about one entry.
+#[derive(Debug, Clone, Copy)]
+struct Meta {
+    cost: f64,
+    size: usize,
+    frequency: u64,
+}
+
+impl Default for Meta {
+    fn default() -> Self {
+        Self {
+            cost: 1.0,
+            size: 1,
+            frequency: 0,
+        }
+    }
+}
+
+/// GreedyDual-Size-Frequency, after Cherkasova: cost-aware eviction.
+///
+/// Each entry has a cost, what it takes to fetch it again after a miss, and
+/// a size. Its priority is `L + frequency * cost / size`, where `frequency`
+/// counts its insertion and hits, and the victim is the entry with the
+/// lowest priority. The inflation value `L` starts at zero and rises to the
+/// priority of each evicted entry, so entries that stop being used age
+/// relative to those inserted or hit since, however costly they were.
+///
+/// Entries are given a cost and a size through
+/// [`put_with_cost`](Cache::put_with_cost), and the size is also their
+/// weight against the capacity. Entries inserted any other way cost 1 and
+/// have size 1, whatever their weight. Overwriting an entry counts as a hit.
+/// The priorities live in an indexed binary heap, so every operation takes
+/// logarithmic time. Iteration ends with the next victim; otherwise its
+/// order is unspecified.
+///
+/// ```
+/// use lru_cache_exercise::GdsfCache;
+///
+/// let mut cache = GdsfCache::new(2);
+/// cache.put_with_cost("report", "...", 2_000.0, 1);
+/// cache.put_with_cost("greeting", "hello", 1.0, 1);
+/// cache.put_with_cost("motd", "hi", 1.0, 1);
+/// assert!(cache.contains_key("report"));
+/// assert!(!cache.contains_key("greeting"));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Gdsf {
+    heap: IndexedHeap,
+    /// The cost, size and frequency of each tracked slot, indexed by slot.
+    metas: Vec<Meta>,
+    inflation: f64,
+    /// The cost and size given for the entry being written, if any.
+    pending: Option<(f64, usize)>,
+}
+
+impl Gdsf {
+    /// Creates a policy that tracks no slots.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the inflation value `L`.
+    pub fn inflation(&self) -> f64 {
+        self.inflation
+    }
+
+    fn priority(&self, meta: &Meta) -> f64 {
+        self.inflation + meta.frequency as f64 * meta.cost / meta.size.max(1) as f64
+    }
+
+    /// Applies the pending cost and size, if any, to `meta`.
+    fn apply_pending(&self, meta: &mut Meta) {
+        if let Some((cost, size)) = self.pending {
+            meta.cost = cost;
+            meta.size = size;
+        }
+    }
+}
+
+impl Policy for Gdsf {
+    fn on_insert(&mut self, slot: usize, _hash: u64) {
+        if slot >= self.metas.len() {
+            self.metas.resize(slot + 1, Meta::default());
+        }
+        let mut meta = Meta {
+            frequency: 1,
+            ..Meta::default()
+        };
+        self.apply_pending(&mut meta);
+        self.metas[slot] = meta;
+        self.heap.push(slot, self.priority(&meta));
+    }
+
+    fn on_hit(&mut self, slot: usize) {
+        let mut meta = self.metas[slot];
+        meta.frequency += 1;
+        self.apply_pending(&mut meta);
+        self.metas[slot] = meta;
+        self.heap.update(slot, self.priority(&meta));
+    }
+
+    fn on_remove(&mut self, slot: usize) {
+        self.heap.remove(slot);
+    }
+
+    fn on_evict(&mut self, slot: usize, _hash: u64) {
+        self.inflation = self.inflation.max(self.heap.priority(slot));
+        self.on_remove(slot);
+    }
+
+    fn victim(&mut self) -> Option<usize> {
+        self.heap.peek()
+    }
+
+    fn victim_excluding(&mut self, keep: usize) -> Option<usize> {
+        match self.heap.peek()? {
+            victim if victim == keep => self.heap.peek_second(),
+            victim => Some(victim),
+        }
+    }
+
+    fn first(&self) -> Option<usize> {
+        self.heap.get(self.heap.len().checked_sub(1)?)
+    }
+
+    fn last(&self) -> Option<usize> {
+        self.heap.peek()
+    }
+
+    fn next(&self, slot: usize) -> Option<usize> {
+        self.heap.get(self.heap.position(slot).checked_sub(1)?)
+    }
+
+    fn prev(&self, slot: usize) -> Option<usize> {
+        self.heap.get(self.heap.position(slot) + 1)
+    }
+
+    fn relocate(&mut self, from: usize, to: usize) {
+        if to >= self.metas.len() {
+            self.metas.resize(to + 1, Meta::default());
+        }
+        self.metas[to] = self.metas[from];
+        self.heap.relocate(from, to);
+    }
+
+    fn clear(&mut self) {
+        self.heap.clear();
+        self.metas.clear();
+        self.inflation = 0.0;
+        self.pending = None;
+    }
+
+    fn shrink_to(&mut self, slots: usize) {
+        self.heap.truncate(slots);
+        self.metas.truncate(slots);
+        self.metas.shrink_to_fit();
+    }
+}
+
+impl<K: Hash + Eq, V> Cache<K, V, Gdsf> {
+    /// Like [`put`](Self::put), but gives the entry a miss `cost` and a
+    /// `size`. The size is the entry's weight against the capacity in place
+    /// of the weigher's.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `cost` is negative or not finite.
+    pub fn put_with_cost(
+        &mut self,
+        key: K,
+        value: V,
+        cost: f64,
+        size: usize,
+    ) -> Vec<Displaced<K, V>> {
+        assert!(
+            cost.is_finite() && cost >= 0.0,
+            "the cost must be finite and non-negative"
+        );
+        self.policy_mut().pending = Some((cost, size));
+        let pending = ClearPending(self);
+        let expiry = pending.0.expiry();
+        pending.0.put_weighted(key, value, size, expiry)
+    }
+
+    /// Returns the priority of `key`, if the cache holds it.
+    pub fn priority<Q>(&self, key: &Q) -> Option<f64>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let (index, _, _) = self.peek_indexed(key)?;
+        Some(self.policy().heap.priority(index))
+    }
+}
+
+/// Forgets the pending cost and size when dropped, so that a panic partway
+/// through [`put_with_cost`](Cache::put_with_cost), in a removal listener
+/// for instance, does not pass them on to the next entry written.
+struct ClearPending<'a, K, V>(&'a mut Cache<K, V, Gdsf>);
+
+impl<K, V> Drop for ClearPending<'_, K, V> {
+    fn drop(&mut self) {
+        self.0.policy_mut().pending = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_costly_entries_outlive_cheap_ones() {
+        let mut gdsf = GdsfCache::new(10);
+        for key in 0..5 {
+            gdsf.put_with_cost(key, (), 100.0, 1);
+        }
+        for key in 100..200 {
+            gdsf.put_with_cost(key, (), 1.0, 1);
+        }
+        assert!((0..5).all(|key| gdsf.contains_key(&key)));
+        assert_eq!(gdsf.policy().inflation(), 19.0);
+
+        // Unused, even costly entries age out once L catches up with them.
+        for key in 200..1_000 {
+            gdsf.put_with_cost(key, (), 1.0, 1);
+        }
+        assert!((0..5).all(|key| !gdsf.contains_key(&key)));
+    }
+
+    #[test]
+    fn test_size_divides_priority_and_counts_as_weight() {
+        let mut cache = GdsfCache::new(4);
+        cache.put_with_cost("big", (), 6.0, 3);
+        cache.put_with_cost("small", (), 3.0, 1);
+        assert_eq!(cache.priority("big"), Some(2.0));
+        assert_eq!(cache.priority("small"), Some(3.0));
+        assert_eq!(
+            cache.put_with_cost("tiny", (), 2.5, 1),
+            [Displaced::Evicted("big", ())]
+        );
+        assert_eq!(cache.policy().inflation(), 2.0);
+        assert_eq!(cache.priority("tiny"), Some(4.5));
+        assert_eq!(cache.keys().copied().collect::<Vec<_>>(), ["tiny", "small"]);
+        assert_eq!(cache.put_with_cost("huge", (), 1.0, 5).len(), 1);
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn test_overwrite_under_pressure_keeps_frequency() {
+        let mut cache = GdsfCache::new(3);
+        cache.put_with_cost("a", (), 1.0, 1);
+        cache.put_with_cost("b", (), 100.0, 2);
+        cache.get("a");
+        // a grows and has the lowest priority, but it is the entry being
+        // written, so b makes room instead.
+        assert_eq!(
+            cache.put_with_cost("a", (), 1.0, 2),
+            [Displaced::Replaced(()), Displaced::Evicted("b", ())]
+        );
+        assert_eq!(cache.priority("a"), Some(1.5));
+        assert_eq!(cache.policy().inflation(), 50.0);
+    }
+
+    #[test]
+    fn test_panicking_put_does_not_leak_its_cost() {
+        use crate::RemovalCause;
+        use std::panic::{self, AssertUnwindSafe};
+
+        let mut cache = GdsfCache::new(1)
+            .with_removal_listener(|&key: &&str, _: &(), _: RemovalCause| assert_ne!(key, "a"));
+        cache.put_with_cost("a", (), 100.0, 1);
+        let put = panic::catch_unwind(AssertUnwindSafe(|| {
+            cache.put_with_cost("b", (), 50.0, 1);
+        }));
+        assert!(put.is_err());
+        cache.put("c", ());
+        assert_eq!(cache.priority("c"), Some(cache.policy().inflation() + 1.0));
+    }
+
+    #[test]
+    fn test_hits_raise_priority_and_inflation_ages_entries() {
+        let mut cache = GdsfCache::new(2);
+        cache.put("a", ());
+        cache.put("b", ());
+        cache.get("a");
+        cache.get("a");
+        assert_eq!(cache.priority("a"), Some(3.0));
+        assert_eq!(cache.put("c", ()), [Displaced::Evicted("b", ())]);
+        assert_eq!(cache.put("d", ()), [Displaced::Evicted("c", ())]);
+        // Each eviction raised L by one, so d already matches a, and the
+        // tie goes against a, whose priority was set longer ago.
+        assert_eq!(cache.policy().inflation(), 2.0);
+        assert_eq!(cache.priority("d"), Some(3.0));
+        assert_eq!(cache.put("e", ()), [Displaced::Evicted("a", ())]);
+    }
+}
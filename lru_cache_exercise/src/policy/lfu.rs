@@ -0,0 +1,397 @@
+use std::borrow::Borrow;
+use std::hash::Hash;
+
+use super::Policy;
+use crate::cache::Cache;
+use crate::list::{Links, List};
+
+/// A [`Cache`] that evicts the least frequently used entry.
+pub type LfuCache<K, V> = Cache<K, V, Lfu>;
+
+/// Evicts the least frequently used entry, breaking ties by recency.
+///
+/// Every entry has a use count, starting at one on insertion. Entries with
+/// the same count share a bucket that is itself an LRU list, and the buckets
+/// are kept in a list ordered by count, so reads, writes and evictions all
+/// take constant time. The victim is the least recently used entry of the
+/// lowest bucket.
+///
+/// Plain LFU never forgets: a key that was hot long ago keeps its high count
+/// and can stay resident forever. [`with_decay`](Self::with_decay) halves
+/// every count periodically so that old popularity fades.
+///
+/// Iteration runs from the highest count to the lowest, and from most to
+/// least recently used within a count.
+///
+/// ```
+/// use lru_cache_exercise::LfuCache;
+///
+/// let mut cache = LfuCache::new(2);
+/// cache.put("hot", 1);
+/// cache.get("hot");
+/// for key in ["scan-1", "scan-2", "scan-3"] {
+///     cache.put(key, 0);
+/// }
+/// assert!(cache.contains_key("hot"));
+/// assert_eq!(cache.frequency("hot"), Some(2));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Lfu {
+    /// Links between the slots of one bucket.
+    links: Links,
+    /// The bucket and last use of each tracked slot, indexed by slot.
+    tracked: Vec<Tracked>,
+    buckets: Vec<Bucket>,
+    /// Links between buckets, in increasing order of count.
+    bucket_links: Links,
+    bucket_order: List,
+    free_buckets: Vec<usize>,
+    /// Counts uses, to order entries by recency when buckets merge.
+    clock: u64,
+    decay: Option<Decay>,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Tracked {
+    bucket: usize,
+    last_use: u64,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Bucket {
+    frequency: u64,
+    slots: List,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Decay {
+    period: u64,
+    remaining: u64,
+}
+
+impl Lfu {
+    /// Creates a policy that tracks no slots and never decays its counts.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Halves every use count, rounding up, after each `period` reads and
+    /// writes. The halving takes time linear in the number of entries, so a
+    /// period of at least the cache's capacity keeps it constant on average.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `period` is zero.
+    pub fn with_decay(mut self, period: u64) -> Self {
+        assert!(period > 0, "the decay period must be non-zero");
+        self.decay = Some(Decay {
+            period,
+            remaining: period,
+        });
+        self
+    }
+
+    /// Returns the use count of the entry in `slot`, which must be tracked.
+    pub(crate) fn frequency(&self, slot: usize) -> u64 {
+        self.buckets[self.tracked[slot].bucket].frequency
+    }
+
+    /// Returns a bucket for `frequency` right after `anchor`, or at the
+    /// bottom of the order when there is no anchor, creating it if needed.
+    fn bucket_after(&mut self, anchor: Option<usize>, frequency: u64) -> usize {
+        let next = match anchor {
+            Some(anchor) => self.bucket_links.next(anchor),
+            None => self.bucket_order.head(),
+        };
+        if let Some(next) = next.filter(|&next| self.buckets[next].frequency == frequency) {
+            return next;
+        }
+        let bucket = Bucket {
+            frequency,
+            slots: List::default(),
+        };
+        let index = match self.free_buckets.pop() {
+            Some(index) => {
+                self.buckets[index] = bucket;
+                index
+            }
+            None => {
+                self.buckets.push(bucket);
+                self.buckets.len() - 1
+            }
+        };
+        match anchor {
+            Some(anchor) => self
+                .bucket_links
+                .insert_after(&mut self.bucket_order, anchor, index),
+            None => self.bucket_links.push_front(&mut self.bucket_order, index),
+        }
+        index
+    }
+
+    /// Links `slot` at the front of `bucket` as its latest use.
+    fn enter(&mut self, slot: usize, bucket: usize) {
+        self.clock += 1;
+        self.tracked[slot] = Tracked {
+            bucket,
+            last_use: self.clock,
+        };
+        self.links.push_front(&mut self.buckets[bucket].slots, slot);
+    }
+
+    /// Unlinks `slot` from its bucket, dropping the bucket if it empties.
+    fn leave(&mut self, slot: usize) {
+        let bucket = self.tracked[slot].bucket;
+        self.links.unlink(&mut self.buckets[bucket].slots, slot);
+        if self.buckets[bucket].slots.is_empty() {
+            self.bucket_links.unlink(&mut self.bucket_order, bucket);
+            self.free_buckets.push(bucket);
+        }
+    }
+
+    fn count_use(&mut self) {
+        let Some(decay) = &mut self.decay else { return };
+        decay.remaining -= 1;
+        if decay.remaining == 0 {
+            decay.remaining = decay.period;
+            self.halve();
+        }
+    }
+
+    /// Halves every count. Buckets whose counts meet merge, ordered by each
+    /// entry's last use.
+    fn halve(&mut self) {
+        let mut groups: Vec<(u64, Vec<usize>)> = Vec::new();
+        let mut cursor = self.bucket_order.head();
+        while let Some(bucket) = cursor {
+            cursor = self.bucket_links.next(bucket);
+            let frequency = self.buckets[bucket].frequency.div_ceil(2);
+            let mut slots = Vec::new();
+            let mut slot = self.buckets[bucket].slots.head();
+            while let Some(index) = slot {
+                slots.push(index);
+                slot = self.links.next(index);
+            }
+            match groups.last_mut() {
+                Some((last, merged)) if *last == frequency => merged.extend(slots),
+                _ => groups.push((frequency, slots)),
+            }
+        }
+
+        self.links.clear();
+        self.buckets.clear();
+        self.bucket_links.clear();
+        self.bucket_order = List::default();
+        self.free_buckets.clear();
+        for (frequency, mut slots) in groups {
+            slots.sort_unstable_by_key(|&slot| self.tracked[slot].last_use);
+            let bucket = self.buckets.len();
+            self.buckets.push(Bucket {
+                frequency,
+                slots: List::default(),
+            });
+            self.bucket_links.push_back(&mut self.bucket_order, bucket);
+            for slot in slots {
+                self.tracked[slot].bucket = bucket;
+                self.links.push_front(&mut self.buckets[bucket].slots, slot);
+            }
+        }
+    }
+}
+
+impl Policy for Lfu {
+    fn on_insert(&mut self, slot: usize, _hash: u64) {
+        if slot >= self.tracked.len() {
+            self.tracked.resize(slot + 1, Tracked::default());
+        }
+        let bucket = self.bucket_after(None, 1);
+        self.enter(slot, bucket);
+        self.count_use();
+    }
+
+    fn on_hit(&mut self, slot: usize) {
+        let bucket = self.tracked[slot].bucket;
+        let frequency = self.buckets[bucket].frequency.saturating_add(1);
+        let next = self.bucket_after(Some(bucket), frequency);
+        self.leave(slot);
+        self.enter(slot, next);
+        self.count_use();
+    }
+
+    fn on_remove(&mut self, slot: usize) {
+        self.leave(slot);
+    }
+
+    fn victim(&mut self) -> Option<usize> {
+        self.last()
+    }
+
+    fn first(&self) -> Option<usize> {
+        self.buckets[self.bucket_order.tail()?].slots.head()
+    }
+
+    fn last(&self) -> Option<usize> {
+        self.buckets[self.bucket_order.head()?].slots.tail()
+    }
+
+    fn next(&self, slot: usize) -> Option<usize> {
+        self.links.next(slot).or_else(|| {
+            let lower = self.bucket_links.prev(self.tracked[slot].bucket)?;
+            self.buckets[lower].slots.head()
+        })
+    }
+
+    fn prev(&self, slot: usize) -> Option<usize> {
+        self.links.prev(slot).or_else(|| {
+            let higher = self.bucket_links.next(self.tracked[slot].bucket)?;
+            self.buckets[higher].slots.tail()
+        })
+    }
+
+    fn relocate(&mut self, from: usize, to: usize) {
+        if to >= self.tracked.len() {
+            self.tracked.resize(to + 1, Tracked::default());
+        }
+        let tracked = self.tracked[from];
+        self.tracked[to] = tracked;
+        self.links
+            .relocate(&mut self.buckets[tracked.bucket].slots, from, to);
+    }
+
+    fn clear(&mut self) {
+        self.links.clear();
+        self.tracked.clear();
+        self.buckets.clear();
+        self.bucket_links.clear();
+        self.bucket_order = List::default();
+        self.free_buckets.clear();
+    }
+
+    fn shrink_to(&mut self, slots: usize) {
+        self.links.truncate(slots);
+        self.tracked.truncate(slots);
+        self.tracked.shrink_to_fit();
+    }
+}
+
+impl<K: Hash + Eq, V> Cache<K, V, Lfu> {
+    /// Returns the use count of `key`, without counting this as a use.
+    pub fn frequency<Q>(&self, key: &Q) -> Option<u64>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let (index, _, _) = self.peek_indexed(key)?;
+        Some(self.policy().frequency(index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Displaced;
+
+    #[test]
+    fn test_evicts_least_frequent() {
+        let mut cache = LfuCache::new(3);
+        cache.put(1, 1);
+        cache.put(2, 2);
+        cache.put(3, 3);
+        cache.get(&1);
+        cache.get(&1);
+        cache.get(&3);
+        assert_eq!(cache.put(4, 4), [Displaced::Evicted(2, 2)]);
+        assert_eq!(cache.put(5, 5), [Displaced::Evicted(4, 4)]);
+        assert_eq!(cache.frequency(&1), Some(3));
+        assert_eq!(cache.frequency(&3), Some(2));
+        assert_eq!(cache.keys().copied().collect::<Vec<_>>(), [1, 3, 5]);
+    }
+
+    #[test]
+    fn test_ties_break_by_recency() {
+        let mut cache = LfuCache::new(3);
+        for key in [1, 2, 3] {
+            cache.put(key, ());
+        }
+        for key in [2, 1, 3] {
+            cache.get(&key);
+        }
+        assert_eq!(cache.keys().copied().collect::<Vec<_>>(), [3, 1, 2]);
+        assert_eq!(cache.put(4, ()), [Displaced::Evicted(2, ())]);
+    }
+
+    #[test]
+    fn test_scan_does_not_flush_hot_keys() {
+        let mut cache = LfuCache::new(10);
+        for round in 0..3 {
+            for key in 0..5 {
+                cache.put(key, round);
+            }
+        }
+        for key in 100..1_000 {
+            cache.put(key, 0);
+        }
+        assert!((0..5).all(|key| cache.contains_key(&key)));
+    }
+
+    #[test]
+    fn test_decay_lets_old_favourites_go() {
+        let run = |policy: Lfu| {
+            let mut cache = Cache::with_policy(2, policy);
+            cache.put("old", ());
+            for _ in 0..50 {
+                cache.get("old");
+            }
+            // The workload moves on to a new key, used less than "old" was.
+            cache.put("new", ());
+            for _ in 0..16 {
+                cache.get("new");
+            }
+            cache.put("next", ());
+            cache.contains_key("old")
+        };
+        assert!(run(Lfu::new()));
+        assert!(!run(Lfu::new().with_decay(8)));
+    }
+
+    #[test]
+    fn test_halving_merges_buckets_by_recency() {
+        let mut cache = Cache::with_policy(4, Lfu::new().with_decay(10));
+        cache.put(1, ());
+        cache.put(2, ());
+        cache.put(3, ());
+        cache.get(&3);
+        cache.get(&2);
+        cache.get(&2);
+        cache.get(&1);
+        cache.get(&3);
+        cache.get(&3);
+        // Counts are now 3: 4, 2: 3, 1: 2. The tenth use halves them.
+        cache.put(4, ());
+        assert_eq!(cache.frequency(&3), Some(2));
+        assert_eq!(cache.frequency(&2), Some(2));
+        assert_eq!(cache.frequency(&1), Some(1));
+        assert_eq!(cache.frequency(&4), Some(1));
+        assert_eq!(cache.keys().copied().collect::<Vec<_>>(), [3, 2, 4, 1]);
+        assert_eq!(
+            cache.keys().rev().copied().collect::<Vec<_>>(),
+            [1, 4, 2, 3]
+        );
+    }
+
+    #[test]
+    fn test_shrink_to_fit_keeps_counts() {
+        let mut cache = LfuCache::new(8);
+        for key in 0..8 {
+            cache.put(key, ());
+        }
+        cache.get(&7);
+        cache.get(&7);
+        for key in 0..6 {
+            cache.remove(&key);
+        }
+        cache.shrink_to_fit();
+        assert_eq!(cache.frequency(&7), Some(3));
+        assert_eq!(cache.keys().copied().collect::<Vec<_>>(), [7, 6]);
+    }
+}
@@ -0,0 +1,520 @@
+use hashbrown::HashTable;
+
+use super::Policy;
+use crate::cache::Cache;
+use crate::list::{Links, List};
+
+/// A [`Cache`] using LIRS.
+pub type LirsCache<K, V> = Cache<K, V, Lirs>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Status {
+    /// Resident, with a low inter-reference recency.
+    Lir,
+    /// Resident, with a high inter-reference recency; in the queue.
+    Hir,
+    /// Evicted, but still in the stack; remembered by hash only.
+    NonResident,
+}
+
+#[derive(Debug, Clone)]
+struct Block {
+    hash: u64,
+    /// The cache slot of a resident block.
+    slot: Option<usize>,
+    status: Status,
+    in_stack: bool,
+}
+
+/// Low Inter-reference Recency Set (LIRS), after Jiang and Zhang.
+///
+/// LIRS ranks keys by the recency of their previous use rather than their
+/// last one. Most of the capacity goes to LIR keys, whose last two uses were
+/// close together; the rest, by default 1%, holds HIR keys in a FIFO queue
+/// from which victims are taken. The stack S orders recently used keys,
+/// resident or not, by recency, and always has an LIR key at the bottom:
+/// HIR keys that sink below the lowest LIR key are pruned from it.
+///
+/// A key used again while still in S has been reused more recently than
+/// the bottom LIR key, so it becomes LIR and that key is demoted to the
+/// queue. This holds for evicted keys too, which stay in S as non-resident
+/// entries, remembered by hash only, for up to a capacity's worth of
+/// evictions. A looping access pattern larger than the cache, under which
+/// LRU never hits, therefore keeps most of the loop resident.
+///
+/// Iteration runs through the LIR entries from the top of S to the bottom,
+/// then the queue from newest to oldest.
+///
+/// ```
+/// use lru_cache_exercise::LirsCache;
+///
+/// let mut cache = LirsCache::new(10);
+/// for _ in 0..10 {
+///     for key in 0..12 {
+///         if cache.get(&key).is_none() {
+///             cache.put(key, ());
+///         }
+///     }
+/// }
+/// assert!(cache.stats().hits >= 80);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Lirs {
+    blocks: Vec<Block>,
+    free: Vec<usize>,
+    /// The stack S, top first, linked by block number.
+    stack_links: Links,
+    stack: List,
+    /// The queue of resident HIR blocks, newest first, and the list of
+    /// non-resident blocks, most recently evicted first. No block is in
+    /// both, so they share one table of links.
+    queue_links: Links,
+    queue: List,
+    non_resident: List,
+    /// The block of each tracked slot, indexed by slot.
+    block_of: Vec<usize>,
+    /// The non-resident blocks, by hash.
+    ghosts: HashTable<usize>,
+    /// The non-resident block of the key being inserted, if it had one.
+    incoming: Option<usize>,
+    lir_len: usize,
+    hir_len: usize,
+    hir_ratio: f64,
+    lir_capacity: usize,
+    non_resident_capacity: usize,
+}
+
+impl Lirs {
+    /// Creates a policy that gives HIR keys 1% of the capacity.
+    pub fn new() -> Self {
+        Self::with_hir_ratio(0.01)
+    }
+
+    /// Creates a policy that gives HIR keys `ratio` of the capacity, and at
+    /// least one entry, as long as LIR keys keep at least one entry too.
+    ///
+    /// # Panics
+    ///
+    /// Panics unless `ratio` is between 0 and 1.
+    pub fn with_hir_ratio(ratio: f64) -> Self {
+        assert!(
+            (0.0..=1.0).contains(&ratio),
+            "the HIR ratio must be between 0 and 1"
+        );
+        Self {
+            blocks: Vec::new(),
+            free: Vec::new(),
+            stack_links: Links::default(),
+            stack: List::default(),
+            queue_links: Links::default(),
+            queue: List::default(),
+            non_resident: List::default(),
+            block_of: Vec::new(),
+            ghosts: HashTable::new(),
+            incoming: None,
+            lir_len: 0,
+            hir_len: 0,
+            hir_ratio: ratio,
+            lir_capacity: 0,
+            non_resident_capacity: 0,
+        }
+    }
+
+    /// Returns the number of LIR entries.
+    pub fn lir_len(&self) -> usize {
+        self.lir_len
+    }
+
+    /// Returns the number of resident HIR entries, those in the queue.
+    pub fn hir_len(&self) -> usize {
+        self.hir_len
+    }
+
+    /// Returns the number of evicted keys still remembered in the stack.
+    pub fn non_resident_len(&self) -> usize {
+        self.ghosts.len()
+    }
+
+    fn new_block(&mut self, block: Block) -> usize {
+        match self.free.pop() {
+            Some(id) => {
+                self.blocks[id] = block;
+                id
+            }
+            None => {
+                self.blocks.push(block);
+                self.blocks.len() - 1
+            }
+        }
+    }
+
+    /// Takes `id` off the stack, forgetting it unless it is a resident HIR
+    /// block, which stays in the queue.
+    fn pop_stack(&mut self, id: usize) {
+        self.stack_links.unlink(&mut self.stack, id);
+        self.blocks[id].in_stack = false;
+        if self.blocks[id].status == Status::NonResident {
+            if self.incoming == Some(id) {
+                self.incoming = None;
+            }
+            self.forget_ghost(id);
+            self.free.push(id);
+        }
+    }
+
+    /// Drops the non-resident block `id` from the ghost index and list.
+    fn forget_ghost(&mut self, id: usize) {
+        let hash = self.blocks[id].hash;
+        if let Ok(entry) = self.ghosts.find_entry(hash, |&ghost| ghost == id) {
+            entry.remove();
+            self.queue_links.unlink(&mut self.non_resident, id);
+        }
+    }
+
+    /// Removes HIR blocks from the bottom of the stack until an LIR block
+    /// is at the bottom.
+    fn prune(&mut self) {
+        while let Some(bottom) = self.stack.tail() {
+            if self.blocks[bottom].status == Status::Lir {
+                break;
+            }
+            self.pop_stack(bottom);
+        }
+    }
+
+    /// Moves `id`, which may or may not be in the stack, to its top.
+    fn push_stack(&mut self, id: usize) {
+        if self.blocks[id].in_stack {
+            self.stack_links.move_to_front(&mut self.stack, id);
+        } else {
+            self.blocks[id].in_stack = true;
+            self.stack_links.push_front(&mut self.stack, id);
+        }
+    }
+
+    /// Turns LIR blocks from the bottom of the stack into HIR blocks at the
+    /// front of the queue until the LIR blocks fit their share.
+    fn demote_overflow(&mut self) {
+        while self.lir_len > self.lir_capacity {
+            // Until the first LIR block, HIR blocks can sit at the bottom.
+            self.prune();
+            let Some(bottom) = self.stack.tail() else {
+                break;
+            };
+            self.blocks[bottom].status = Status::Hir;
+            self.lir_len -= 1;
+            self.hir_len += 1;
+            self.queue_links.push_front(&mut self.queue, bottom);
+            self.pop_stack(bottom);
+        }
+        self.prune();
+    }
+
+    /// Makes the resident block `id` an LIR block at the top of the stack.
+    fn make_lir(&mut self, id: usize) {
+        if self.blocks[id].status == Status::Hir {
+            self.queue_links.unlink(&mut self.queue, id);
+            self.hir_len -= 1;
+        }
+        self.blocks[id].status = Status::Lir;
+        self.lir_len += 1;
+        self.push_stack(id);
+        self.demote_overflow();
+    }
+
+    /// Forgets the oldest non-resident blocks beyond the limit.
+    fn trim_ghosts(&mut self) {
+        while self.ghosts.len() > self.non_resident_capacity {
+            let Some(oldest) = self.non_resident.tail() else {
+                break;
+            };
+            self.pop_stack(oldest);
+        }
+    }
+
+    /// Returns the first LIR block at or below `id` in the stack.
+    fn lir_down(&self, mut id: Option<usize>) -> Option<usize> {
+        while let Some(block) = id {
+            if self.blocks[block].status == Status::Lir {
+                return Some(block);
+            }
+            id = self.stack_links.next(block);
+        }
+        None
+    }
+
+    /// Returns the first LIR block at or above `id` in the stack.
+    fn lir_up(&self, mut id: Option<usize>) -> Option<usize> {
+        while let Some(block) = id {
+            if self.blocks[block].status == Status::Lir {
+                return Some(block);
+            }
+            id = self.stack_links.prev(block);
+        }
+        None
+    }
+
+    fn slot(&self, id: Option<usize>) -> Option<usize> {
+        self.blocks[id?].slot
+    }
+}
+
+impl Default for Lirs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Policy for Lirs {
+    fn set_capacity(&mut self, capacity: usize) {
+        let hir_capacity = ((capacity as f64 * self.hir_ratio) as usize).max(1);
+        // Without an LIR block, nothing anchors the bottom of the stack.
+        self.lir_capacity = capacity.saturating_sub(hir_capacity).max(capacity.min(1));
+        self.non_resident_capacity = capacity;
+        self.demote_overflow();
+        self.trim_ghosts();
+    }
+
+    fn before_insert(&mut self, hash: u64) {
+        let blocks = &self.blocks;
+        let Ok(entry) = self
+            .ghosts
+            .find_entry(hash, |&ghost| blocks[ghost].hash == hash)
+        else {
+            return;
+        };
+        let (id, _) = entry.remove();
+        self.queue_links.unlink(&mut self.non_resident, id);
+        self.incoming = Some(id);
+    }
+
+    fn on_insert(&mut self, slot: usize, hash: u64) {
+        if slot >= self.block_of.len() {
+            self.block_of.resize(slot + 1, 0);
+        }
+        if let Some(id) = self.incoming.take() {
+            // Reused while still in the stack: a low inter-reference recency.
+            self.blocks[id].slot = Some(slot);
+            self.block_of[slot] = id;
+            self.make_lir(id);
+            return;
+        }
+        let lir = self.lir_len < self.lir_capacity;
+        let id = self.new_block(Block {
+            hash,
+            slot: Some(slot),
+            status: if lir { Status::Lir } else { Status::Hir },
+            in_stack: true,
+        });
+        self.block_of[slot] = id;
+        self.stack_links.push_front(&mut self.stack, id);
+        if lir {
+            self.lir_len += 1;
+        } else {
+            self.hir_len += 1;
+            self.queue_links.push_front(&mut self.queue, id);
+        }
+    }
+
+    fn on_hit(&mut self, slot: usize) {
+        let id = self.block_of[slot];
+        match self.blocks[id].status {
+            Status::Lir => {
+                self.push_stack(id);
+                self.prune();
+            }
+            Status::Hir if self.blocks[id].in_stack => self.make_lir(id),
+            _ => {
+                self.push_stack(id);
+                self.queue_links.unlink(&mut self.queue, id);
+                self.queue_links.push_front(&mut self.queue, id);
+            }
+        }
+    }
+
+    fn on_remove(&mut self, slot: usize) {
+        let id = self.block_of[slot];
+        match self.blocks[id].status {
+            Status::Lir => self.lir_len -= 1,
+            _ => {
+                self.hir_len -= 1;
+                self.queue_links.unlink(&mut self.queue, id);
+            }
+        }
+        if self.blocks[id].in_stack {
+            self.stack_links.unlink(&mut self.stack, id);
+        }
+        self.free.push(id);
+        self.prune();
+    }
+
+    fn on_evict(&mut self, slot: usize, hash: u64) {
+        let id = self.block_of[slot];
+        if self.blocks[id].status != Status::Hir || !self.blocks[id].in_stack {
+            self.on_remove(slot);
+            return;
+        }
+        self.hir_len -= 1;
+        self.queue_links.unlink(&mut self.queue, id);
+        let block = &mut self.blocks[id];
+        block.slot = None;
+        block.status = Status::NonResident;
+        let blocks = &self.blocks;
+        self.ghosts
+            .insert_unique(hash, id, |&ghost| blocks[ghost].hash);
+        self.queue_links.push_front(&mut self.non_resident, id);
+        self.trim_ghosts();
+    }
+
+    fn victim(&mut self) -> Option<usize> {
+        let id = self.queue.tail().or(self.stack.tail())?;
+        self.blocks[id].slot
+    }
+
+    fn first(&self) -> Option<usize> {
+        let lir = self.lir_down(self.stack.head());
+        self.slot(lir.or(self.queue.head()))
+    }
+
+    fn last(&self) -> Option<usize> {
+        self.slot(self.queue.tail().or(self.lir_up(self.stack.tail())))
+    }
+
+    fn next(&self, slot: usize) -> Option<usize> {
+        let id = self.block_of[slot];
+        if self.blocks[id].status == Status::Lir {
+            let lir = self.lir_down(self.stack_links.next(id));
+            self.slot(lir.or(self.queue.head()))
+        } else {
+            self.slot(self.queue_links.next(id))
+        }
+    }
+
+    fn prev(&self, slot: usize) -> Option<usize> {
+        let id = self.block_of[slot];
+        if self.blocks[id].status == Status::Lir {
+            self.slot(self.lir_up(self.stack_links.prev(id)))
+        } else {
+            let hir = self.queue_links.prev(id);
+            self.slot(hir.or_else(|| self.lir_up(self.stack.tail())))
+        }
+    }
+
+    fn relocate(&mut self, from: usize, to: usize) {
+        if to >= self.block_of.len() {
+            self.block_of.resize(to + 1, 0);
+        }
+        let id = self.block_of[from];
+        self.block_of[to] = id;
+        self.blocks[id].slot = Some(to);
+    }
+
+    fn clear(&mut self) {
+        self.blocks.clear();
+        self.free.clear();
+        self.stack_links.clear();
+        self.stack = List::default();
+        self.queue_links.clear();
+        self.queue = List::default();
+        self.non_resident = List::default();
+        self.block_of.clear();
+        self.ghosts.clear();
+        self.incoming = None;
+        self.lir_len = 0;
+        self.hir_len = 0;
+    }
+
+    fn shrink_to(&mut self, slots: usize) {
+        self.block_of.truncate(slots);
+        self.block_of.shrink_to_fit();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::policy::access;
+    use crate::{Displaced, LruCache};
+
+    #[test]
+    fn test_loop_larger_than_cache_still_hits() {
+        let mut lirs = LirsCache::new(100);
+        let mut lru = LruCache::new(100);
+        for _ in 0..20 {
+            for key in 0..120 {
+                access(&mut lirs, key);
+                access(&mut lru, key);
+            }
+        }
+        assert_eq!(lru.stats().hits, 0);
+        // Past the first lap, the 99 LIR keys hit every time.
+        assert!(lirs.stats().hits >= 19 * 99);
+    }
+
+    #[test]
+    fn test_returning_ghost_becomes_lir() {
+        let mut cache = Cache::with_policy(3, Lirs::with_hir_ratio(0.0));
+        for key in 1..=3 {
+            cache.put(key, ());
+        }
+        assert_eq!((cache.policy().lir_len(), cache.policy().hir_len()), (2, 1));
+        assert_eq!(cache.put(4, ()), [Displaced::Evicted(3, ())]);
+        assert_eq!(cache.policy().non_resident_len(), 1);
+
+        // 3 is still in the stack, above the bottom LIR key 1, which it
+        // pushes into the queue.
+        assert_eq!(cache.put(3, ()), [Displaced::Evicted(4, ())]);
+        assert_eq!(cache.keys().copied().collect::<Vec<_>>(), [3, 2, 1]);
+        assert_eq!(cache.put(5, ()), [Displaced::Evicted(1, ())]);
+        assert_eq!(cache.keys().rev().copied().collect::<Vec<_>>(), [5, 2, 3]);
+    }
+
+    #[test]
+    fn test_tiny_lir_share_keeps_an_lir_block() {
+        let caches = [
+            LirsCache::new(1),
+            Cache::with_policy(4, Lirs::with_hir_ratio(1.0)),
+        ];
+        for mut cache in caches {
+            cache.put(1, ());
+            cache.put(2, ());
+            cache.get(&2);
+            for key in 3..20 {
+                cache.put(key, ());
+                access(&mut cache, key % 3);
+            }
+            let lirs = cache.policy();
+            assert!(cache.len() <= cache.capacity());
+            assert_eq!(lirs.lir_len() + lirs.hir_len(), cache.len());
+            assert_eq!(lirs.lir_len(), 1);
+            let bottom = lirs.stack.tail().unwrap();
+            assert_eq!(lirs.blocks[bottom].status, Status::Lir);
+        }
+    }
+
+    #[test]
+    fn test_stack_stays_bounded() {
+        let mut cache = LirsCache::new(8);
+        for key in 0..2_000 {
+            access(&mut cache, key % 11);
+            access(&mut cache, key);
+            if key % 7 == 0 {
+                cache.remove(&(key / 2));
+            }
+        }
+        let lirs = cache.policy();
+        assert_eq!(lirs.lir_len() + lirs.hir_len(), cache.len());
+        assert!(lirs.non_resident_len() <= 8);
+        let in_use = lirs.blocks.len() - lirs.free.len();
+        assert_eq!(in_use, cache.len() + lirs.non_resident_len());
+        let bottom = lirs.stack.tail().unwrap();
+        assert_eq!(lirs.blocks[bottom].status, Status::Lir);
+
+        cache.shrink_to_fit();
+        let keys: Vec<_> = cache.keys().copied().collect();
+        let mut reversed: Vec<_> = cache.keys().rev().copied().collect();
+        reversed.reverse();
+        assert_eq!(keys.len(), cache.len());
+        assert_eq!(keys, reversed);
+    }
+}
@@ -0,0 +1,177 @@
+use std::hash::Hash;
+use std::iter::successors;
+
+use super::Policy;
+use crate::cache::Cache;
+use crate::list::{Links, List};
+
+/// A [`Cache`] that evicts the least recently used entry.
+pub type LruCache<K, V> = Cache<K, V, Lru>;
+
+/// Evicts the least recently used entry.
+///
+/// Entries sit in a recency list that reads and writes move to the front.
+/// Iteration runs from most to least recently used.
+#[derive(Debug, Clone, Default)]
+pub struct Lru {
+    links: Links,
+    list: List,
+}
+
+impl Lru {
+    /// Creates a policy that tracks no slots.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Policy for Lru {
+    fn on_insert(&mut self, slot: usize, _hash: u64) {
+        self.links.push_front(&mut self.list, slot);
+    }
+
+    fn on_hit(&mut self, slot: usize) {
+        self.links.move_to_front(&mut self.list, slot);
+    }
+
+    fn on_remove(&mut self, slot: usize) {
+        self.links.unlink(&mut self.list, slot);
+    }
+
+    fn victim(&mut self) -> Option<usize> {
+        self.list.tail()
+    }
+
+    fn first(&self) -> Option<usize> {
+        self.list.head()
+    }
+
+    fn last(&self) -> Option<usize> {
+        self.list.tail()
+    }
+
+    fn next(&self, slot: usize) -> Option<usize> {
+        self.links.next(slot)
+    }
+
+    fn prev(&self, slot: usize) -> Option<usize> {
+        self.links.prev(slot)
+    }
+
+    fn relocate(&mut self, from: usize, to: usize) {
+        self.links.relocate(&mut self.list, from, to);
+    }
+
+    fn clear(&mut self) {
+        self.links.clear();
+        self.list = List::default();
+    }
+
+    fn shrink_to(&mut self, slots: usize) {
+        self.links.truncate(slots);
+    }
+}
+
+impl<K, V> Cache<K, V, Lru> {
+    /// Returns the arena index of the most recently used entry.
+    pub(crate) fn mru_index(&self) -> Option<usize> {
+        self.policy().first()
+    }
+}
+
+impl<K: Hash + Eq, V> Cache<K, V, Lru> {
+    /// Returns the least recently used entry without removing or promoting it.
+    /// Skips expired entries.
+    pub fn peek_lru(&self) -> Option<(&K, &V)> {
+        let mut walk = successors(self.policy().last(), |&index| self.policy().prev(index));
+        Some(self.entry_at(walk.find(|&index| !self.expired_at(index))?))
+    }
+
+    /// Returns the most recently used entry without removing it. Skips
+    /// expired entries.
+    pub fn peek_mru(&self) -> Option<(&K, &V)> {
+        let mut walk = successors(self.policy().first(), |&index| self.policy().next(index));
+        Some(self.entry_at(walk.find(|&index| !self.expired_at(index))?))
+    }
+
+    /// Removes and returns the least recently used entry. Expired entries in
+    /// the way are dropped, as if found by a lookup.
+    pub fn pop_lru(&mut self) -> Option<(K, V)> {
+        loop {
+            let index = self.policy().last()?;
+            if !self.expired_at(index) {
+                return Some(self.remove_at(index));
+            }
+            self.purge_at(index);
+        }
+    }
+
+    /// Removes and returns the most recently used entry. Expired entries in
+    /// the way are dropped, as if found by a lookup.
+    pub fn pop_mru(&mut self) -> Option<(K, V)> {
+        loop {
+            let index = self.policy().first()?;
+            if !self.expired_at(index) {
+                return Some(self.remove_at(index));
+            }
+            self.purge_at(index);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{Expiry, ManualClock, RemovalCause};
+
+    #[test]
+    fn test_hits_move_to_front() {
+        let mut lru = Lru::new();
+        for slot in 0..3 {
+            lru.on_insert(slot, 0);
+        }
+        lru.on_hit(0);
+        assert_eq!(lru.victim(), Some(1));
+        lru.on_remove(1);
+        lru.relocate(2, 1);
+        assert_eq!(
+            (lru.first(), lru.next(0), lru.last()),
+            (Some(0), Some(1), Some(1))
+        );
+        assert_eq!(lru.victim(), Some(1));
+    }
+
+    #[test]
+    fn test_peek_and_pop_skip_expired_entries() {
+        let clock = Arc::new(ManualClock::new());
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&log);
+        let mut cache = LruCache::new(4)
+            .with_clock(Arc::clone(&clock))
+            .with_removal_listener(move |&key: &u32, _: &(), cause| {
+                sink.lock().unwrap().push((key, cause));
+            });
+        let short = Expiry::NEVER.with_time_to_live(Duration::from_secs(1));
+        cache.put_with_expiry(1, (), short);
+        cache.put(2, ());
+        cache.put_with_expiry(3, (), short);
+        clock.advance(Duration::from_secs(1));
+
+        assert_eq!(cache.peek_lru(), Some((&2, &())));
+        assert_eq!(cache.peek_mru(), Some((&2, &())));
+        assert_eq!(cache.pop_mru(), Some((2, ())));
+        assert_eq!(cache.pop_lru(), None);
+        assert!(cache.is_empty());
+        assert_eq!(
+            *log.lock().unwrap(),
+            [
+                (3, RemovalCause::Expired),
+                (2, RemovalCause::Explicit),
+                (1, RemovalCause::Expired),
+            ]
+        );
+    }
+}
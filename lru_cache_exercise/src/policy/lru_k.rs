@@ -0,0 +1,322 @@
+use std::collections::BTreeSet;
+use std::ops::Bound;
+
+use hashbrown::HashTable;
+
+use super::Policy;
+use crate::cache::Cache;
+use crate::ghost::GhostQueue;
+
+/// A [`Cache`] using LRU-K.
+pub type LruKCache<K, V> = Cache<K, V, LruK>;
+
+/// The reference history of one key, in ticks of the policy's clock.
+#[derive(Debug, Clone, Default)]
+struct History {
+    /// Uncorrelated reference times, most recent first, at most K of them.
+    references: Vec<u64>,
+    /// The time of the last reference, correlated or not.
+    last: u64,
+}
+
+/// LRU-K, after O'Neil, O'Neil and Weikum.
+///
+/// Each key remembers the times of its last K references, counted in
+/// insertions and hits. The victim is the entry whose K-th most recent
+/// reference is oldest; entries referenced fewer than K times go first,
+/// least recently used first. With K = 2, the default, a key used once
+/// cannot displace a key used twice, so scans pass through without
+/// disturbing the working set.
+///
+/// References within the correlated reference period of the previous one
+/// are treated as part of the same burst: they refresh the entry's last
+/// reference time but add nothing to its history, and an entry is only
+/// evicted while inside its period if every entry is. The period defaults
+/// to zero, which correlates nothing.
+///
+/// Choosing a victim takes logarithmic time, plus a step for every entry
+/// passed over because it is inside its period. Those entries are not kept
+/// apart, so with a period long enough to cover most of the cache each
+/// eviction can take time linear in the number of entries.
+///
+/// The history of an evicted key is kept, by hash, for up to a capacity's
+/// worth of evictions, so that a key evicted between two uses still gets
+/// credit for the first. Iteration runs from the entry with the most recent
+/// K-th reference to the next victim.
+///
+/// ```
+/// use lru_cache_exercise::LruKCache;
+///
+/// let mut cache = LruKCache::new(4);
+/// for key in 0..3 {
+///     cache.put(key, ());
+///     cache.get(&key);
+/// }
+/// for key in 100..200 {
+///     cache.put(key, ());
+/// }
+/// assert!((0..3).all(|key| cache.contains_key(&key)));
+/// ```
+#[derive(Debug, Clone)]
+pub struct LruK {
+    k: usize,
+    correlated_period: u64,
+    clock: u64,
+    /// The history of each tracked slot, indexed by slot.
+    histories: Vec<History>,
+    /// Every tracked slot, ordered by its K-th reference time, with zero
+    /// standing for fewer than K references, then by its last reference.
+    order: BTreeSet<(u64, u64, usize)>,
+    /// The histories of recently evicted keys, by hash.
+    retained: HashTable<(u64, History)>,
+    /// The hashes in `retained`, most recently evicted first.
+    retained_order: GhostQueue,
+    retained_capacity: usize,
+    /// The retained history of the key being inserted, if it had one.
+    incoming: Option<History>,
+}
+
+impl LruK {
+    /// Creates an LRU-2 policy.
+    pub fn new() -> Self {
+        Self::with_k(2)
+    }
+
+    /// Creates a policy that ranks entries by their `k`-th most recent
+    /// reference.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `k` is zero.
+    pub fn with_k(k: usize) -> Self {
+        assert!(k > 0, "K must be non-zero");
+        Self {
+            k,
+            correlated_period: 0,
+            clock: 0,
+            histories: Vec::new(),
+            order: BTreeSet::new(),
+            retained: HashTable::new(),
+            retained_order: GhostQueue::default(),
+            retained_capacity: 0,
+            incoming: None,
+        }
+    }
+
+    /// Treats references that follow the previous one within `period`
+    /// insertions and hits as correlated. The longer the period, the more
+    /// entries each eviction may pass over.
+    pub fn with_correlated_period(mut self, period: u64) -> Self {
+        self.correlated_period = period;
+        self
+    }
+
+    /// Returns K.
+    pub fn k(&self) -> usize {
+        self.k
+    }
+
+    /// Returns the number of evicted keys whose history is still kept.
+    pub fn retained_len(&self) -> usize {
+        self.retained.len()
+    }
+
+    fn key(&self, slot: usize) -> (u64, u64, usize) {
+        let history = &self.histories[slot];
+        let kth = match history.references.get(self.k - 1) {
+            Some(&time) => time,
+            None => 0,
+        };
+        (kth, history.last, slot)
+    }
+
+    /// Adds a reference at the current time to `history`.
+    fn reference(&self, history: &mut History) {
+        let now = self.clock;
+        let correlated =
+            !history.references.is_empty() && now - history.last <= self.correlated_period;
+        if !correlated {
+            history.references.insert(0, now);
+            history.references.truncate(self.k);
+        }
+        history.last = now;
+    }
+
+    fn trim_retained(&mut self) {
+        while self.retained.len() > self.retained_capacity {
+            let Some(hash) = self.retained_order.pop_back() else {
+                break;
+            };
+            if let Ok(entry) = self.retained.find_entry(hash, |&(h, _)| h == hash) {
+                entry.remove();
+            }
+        }
+    }
+}
+
+impl Default for LruK {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Policy for LruK {
+    fn set_capacity(&mut self, capacity: usize) {
+        self.retained_capacity = capacity;
+        self.trim_retained();
+    }
+
+    fn before_insert(&mut self, hash: u64) {
+        self.clock += 1;
+        let Ok(entry) = self.retained.find_entry(hash, |&(h, _)| h == hash) else {
+            return;
+        };
+        let ((_, history), _) = entry.remove();
+        self.retained_order.remove(hash);
+        self.incoming = Some(history);
+    }
+
+    fn on_insert(&mut self, slot: usize, _hash: u64) {
+        if slot >= self.histories.len() {
+            self.histories.resize(slot + 1, History::default());
+        }
+        let mut history = self.incoming.take().unwrap_or_default();
+        self.reference(&mut history);
+        self.histories[slot] = history;
+        self.order.insert(self.key(slot));
+    }
+
+    fn on_hit(&mut self, slot: usize) {
+        self.clock += 1;
+        self.order.remove(&self.key(slot));
+        let mut history = std::mem::take(&mut self.histories[slot]);
+        self.reference(&mut history);
+        self.histories[slot] = history;
+        self.order.insert(self.key(slot));
+    }
+
+    fn on_remove(&mut self, slot: usize) {
+        self.order.remove(&self.key(slot));
+        self.histories[slot] = History::default();
+    }
+
+    fn on_evict(&mut self, slot: usize, hash: u64) {
+        self.order.remove(&self.key(slot));
+        let history = std::mem::take(&mut self.histories[slot]);
+        if self.retained_capacity == 0 {
+            return;
+        }
+        if let Ok(entry) = self.retained.find_entry(hash, |&(h, _)| h == hash) {
+            entry.remove();
+        }
+        self.retained
+            .insert_unique(hash, (hash, history), |&(h, _)| h);
+        self.retained_order.push_front(hash);
+        self.trim_retained();
+    }
+
+    fn victim(&mut self) -> Option<usize> {
+        let now = self.clock;
+        let eligible = self
+            .order
+            .iter()
+            .find(|&&(_, last, _)| now - last > self.correlated_period);
+        eligible.or(self.order.first()).map(|&(_, _, slot)| slot)
+    }
+
+    fn victim_excluding(&mut self, keep: usize) -> Option<usize> {
+        let now = self.clock;
+        let mut others = self.order.iter().filter(|&&(_, _, slot)| slot != keep);
+        let eligible = others
+            .clone()
+            .find(|&&(_, last, _)| now - last > self.correlated_period);
+        eligible.or(others.next()).map(|&(_, _, slot)| slot)
+    }
+
+    fn first(&self) -> Option<usize> {
+        self.order.last().map(|&(_, _, slot)| slot)
+    }
+
+    fn last(&self) -> Option<usize> {
+        self.order.first().map(|&(_, _, slot)| slot)
+    }
+
+    fn next(&self, slot: usize) -> Option<usize> {
+        let key = self.key(slot);
+        self.order
+            .range(..key)
+            .next_back()
+            .map(|&(_, _, slot)| slot)
+    }
+
+    fn prev(&self, slot: usize) -> Option<usize> {
+        let key = self.key(slot);
+        self.order
+            .range((Bound::Excluded(key), Bound::Unbounded))
+            .next()
+            .map(|&(_, _, slot)| slot)
+    }
+
+    fn relocate(&mut self, from: usize, to: usize) {
+        if to >= self.histories.len() {
+            self.histories.resize(to + 1, History::default());
+        }
+        self.order.remove(&self.key(from));
+        self.histories[to] = std::mem::take(&mut self.histories[from]);
+        self.order.insert(self.key(to));
+    }
+
+    fn clear(&mut self) {
+        self.histories.clear();
+        self.order.clear();
+        self.retained.clear();
+        self.retained_order.clear();
+        self.incoming = None;
+    }
+
+    fn shrink_to(&mut self, slots: usize) {
+        self.histories.truncate(slots);
+        self.histories.shrink_to_fit();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::policy::assert_scan_resistant;
+    use crate::Displaced;
+
+    #[test]
+    fn test_scan_does_not_displace_twice_used_keys() {
+        let lru_k = assert_scan_resistant(LruKCache::new(10), 6, 3, 3, 1_000);
+        assert_eq!(lru_k.policy().retained_len(), 10);
+    }
+
+    #[test]
+    fn test_retained_history_counts() {
+        let mut cache = LruKCache::new(2);
+        cache.put("a", ());
+        cache.get("a");
+        cache.put("b", ());
+        assert_eq!(cache.put("c", ()), [Displaced::Evicted("b", ())]);
+        // b comes back with its first reference remembered, ahead of c.
+        assert_eq!(cache.put("b", ()), [Displaced::Evicted("c", ())]);
+        assert_eq!(cache.keys().copied().collect::<Vec<_>>(), ["b", "a"]);
+        assert_eq!(cache.put("d", ()), [Displaced::Evicted("a", ())]);
+    }
+
+    #[test]
+    fn test_correlated_references_are_one_burst() {
+        let burst = |period| {
+            let mut cache = Cache::with_policy(2, LruK::new().with_correlated_period(period));
+            cache.put("a", ());
+            cache.get("a");
+            cache.put("b", ());
+            cache.put("c", ())
+        };
+        assert_eq!(burst(0), [Displaced::Evicted("b", ())]);
+        // The hit on a came right after its insertion, and b is still
+        // inside its own period.
+        assert_eq!(burst(1), [Displaced::Evicted("a", ())]);
+    }
+}
@@ -0,0 +1,156 @@
+use std::hash::{BuildHasher, RandomState};
+
+use super::Policy;
+use crate::cache::Cache;
+
+/// A [`Cache`] that evicts a random entry.
+pub type RandomCache<K, V> = Cache<K, V, Random>;
+
+/// Evicts an entry chosen uniformly at random.
+///
+/// Useful when accesses have no locality to exploit, since it spends nothing
+/// on bookkeeping per read. Iteration order is arbitrary.
+#[derive(Debug, Clone)]
+pub struct Random {
+    /// Every tracked slot, in no particular order.
+    slots: Vec<usize>,
+    /// The position of each tracked slot in `slots`, indexed by slot.
+    positions: Vec<usize>,
+    state: u64,
+}
+
+impl Random {
+    /// Creates a policy seeded from the operating system's randomness.
+    pub fn new() -> Self {
+        Self::with_seed(RandomState::new().hash_one(0u64))
+    }
+
+    /// Creates a policy whose choices are determined by `seed`.
+    pub fn with_seed(seed: u64) -> Self {
+        Self {
+            slots: Vec::new(),
+            positions: Vec::new(),
+            state: seed,
+        }
+    }
+
+    /// Returns the next output of a SplitMix64 generator.
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+}
+
+impl Default for Random {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Policy for Random {
+    fn on_insert(&mut self, slot: usize, _hash: u64) {
+        if slot >= self.positions.len() {
+            self.positions.resize(slot + 1, 0);
+        }
+        self.positions[slot] = self.slots.len();
+        self.slots.push(slot);
+    }
+
+    fn on_hit(&mut self, _slot: usize) {}
+
+    fn on_remove(&mut self, slot: usize) {
+        let position = self.positions[slot];
+        self.slots.swap_remove(position);
+        if let Some(&moved) = self.slots.get(position) {
+            self.positions[moved] = position;
+        }
+    }
+
+    fn victim(&mut self) -> Option<usize> {
+        if self.slots.is_empty() {
+            return None;
+        }
+        let position = self.next_u64() % self.slots.len() as u64;
+        Some(self.slots[position as usize])
+    }
+
+    fn first(&self) -> Option<usize> {
+        self.slots.first().copied()
+    }
+
+    fn last(&self) -> Option<usize> {
+        self.slots.last().copied()
+    }
+
+    fn next(&self, slot: usize) -> Option<usize> {
+        self.slots.get(self.positions[slot] + 1).copied()
+    }
+
+    fn prev(&self, slot: usize) -> Option<usize> {
+        let position = self.positions[slot].checked_sub(1)?;
+        Some(self.slots[position])
+    }
+
+    fn relocate(&mut self, from: usize, to: usize) {
+        if to >= self.positions.len() {
+            self.positions.resize(to + 1, 0);
+        }
+        let position = self.positions[from];
+        self.positions[to] = position;
+        self.slots[position] = to;
+    }
+
+    fn clear(&mut self) {
+        self.slots.clear();
+        self.positions.clear();
+    }
+
+    fn shrink_to(&mut self, slots: usize) {
+        self.slots.shrink_to_fit();
+        self.positions.truncate(slots);
+        self.positions.shrink_to_fit();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_evicts_every_entry_eventually() {
+        let mut cache = Cache::with_policy(4, Random::with_seed(7));
+        let mut evicted = Vec::new();
+        for i in 0..200 {
+            for displaced in cache.put(i, i) {
+                if let crate::Displaced::Evicted(key, _) = displaced {
+                    evicted.push(key);
+                }
+            }
+        }
+        assert_eq!(cache.len(), 4);
+        assert_eq!(evicted.len(), 196);
+        // Unlike LRU or FIFO, the victims do not come in insertion order.
+        assert!(evicted.windows(2).any(|pair| pair[0] > pair[1]));
+        let mut resident: Vec<_> = cache.keys().copied().collect();
+        resident.sort_unstable();
+        resident.dedup();
+        assert_eq!(resident.len(), 4);
+    }
+
+    #[test]
+    fn test_same_seed_same_choices() {
+        let run = |seed| {
+            let mut cache = Cache::with_policy(8, Random::with_seed(seed));
+            for i in 0..64 {
+                cache.put(i, ());
+            }
+            let mut keys: Vec<_> = cache.keys().copied().collect();
+            keys.sort_unstable();
+            keys
+        };
+        assert_eq!(run(1), run(1));
+    }
+}
@@ -0,0 +1,307 @@
+use super::Policy;
+use crate::cache::Cache;
+use crate::ghost::GhostQueue;
+use crate::list::{Links, List};
+
+/// A [`Cache`] using S3-FIFO.
+pub type S3FifoCache<K, V> = Cache<K, V, S3Fifo>;
+
+/// The most hits an entry's frequency counter remembers.
+const MAX_FREQUENCY: u8 = 3;
+
+/// S3-FIFO, after Yang, Zhang, Qiu, Yue and Vinayak: three FIFO queues.
+///
+/// New keys enter the small queue, which holds a tenth of the capacity by
+/// default. Each entry counts its hits, up to three. When the small queue
+/// is at or above its share, its oldest entry is examined: if it was hit
+/// since insertion it moves to the main queue with its count reset, and
+/// otherwise it is evicted and its hash is remembered in the ghost queue.
+/// Otherwise the oldest entry of the main queue is examined: if its count
+/// is non-zero it is decremented and the entry goes back to the front, and
+/// otherwise it is evicted. A key inserted while remembered by the ghost
+/// queue goes straight to the main queue.
+///
+/// Promotion is lazy: a hit only bumps a counter and never moves anything.
+/// Keys used once leave through the small queue without touching the main
+/// queue. The ghost queue remembers as many keys as the main queue's share.
+/// Iteration runs through the main queue and then the small queue, each from
+/// newest to oldest.
+///
+/// ```
+/// use lru_cache_exercise::S3FifoCache;
+///
+/// let mut cache = S3FifoCache::new(10);
+/// cache.put(0, ());
+/// cache.get(&0);
+/// for key in 1..100 {
+///     cache.put(key, ());
+/// }
+/// assert!(cache.contains_key(&0));
+/// assert_eq!(cache.policy().main_len(), 1);
+/// ```
+#[derive(Debug, Clone)]
+pub struct S3Fifo {
+    links: Links,
+    small: List,
+    main: List,
+    small_len: usize,
+    main_len: usize,
+    /// Whether each tracked slot is in the main queue, indexed by slot.
+    in_main: Vec<bool>,
+    /// The hit counter of each tracked slot, indexed by slot.
+    frequencies: Vec<u8>,
+    ghost: GhostQueue,
+    /// Whether the key being inserted was found in the ghost queue.
+    incoming_main: bool,
+    small_ratio: f64,
+    small_capacity: usize,
+    ghost_capacity: usize,
+}
+
+impl S3Fifo {
+    /// Creates a policy whose small queue holds a tenth of the capacity.
+    pub fn new() -> Self {
+        Self::with_small_ratio(0.1)
+    }
+
+    /// Creates a policy whose small queue holds `ratio` of the capacity, and
+    /// at least one entry.
+    ///
+    /// # Panics
+    ///
+    /// Panics unless `ratio` is between 0 and 1.
+    pub fn with_small_ratio(ratio: f64) -> Self {
+        assert!(
+            (0.0..=1.0).contains(&ratio),
+            "the small queue ratio must be between 0 and 1"
+        );
+        Self {
+            links: Links::default(),
+            small: List::default(),
+            main: List::default(),
+            small_len: 0,
+            main_len: 0,
+            in_main: Vec::new(),
+            frequencies: Vec::new(),
+            ghost: GhostQueue::default(),
+            incoming_main: false,
+            small_ratio: ratio,
+            small_capacity: 1,
+            ghost_capacity: 0,
+        }
+    }
+
+    /// Returns the number of entries in the small queue.
+    pub fn small_len(&self) -> usize {
+        self.small_len
+    }
+
+    /// Returns the number of entries in the main queue.
+    pub fn main_len(&self) -> usize {
+        self.main_len
+    }
+
+    /// Returns the number of keys remembered in the ghost queue.
+    pub fn ghost_len(&self) -> usize {
+        self.ghost.len()
+    }
+
+    fn list(&mut self, slot: usize) -> &mut List {
+        if self.in_main[slot] {
+            &mut self.main
+        } else {
+            &mut self.small
+        }
+    }
+}
+
+impl Default for S3Fifo {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Policy for S3Fifo {
+    fn set_capacity(&mut self, capacity: usize) {
+        self.small_capacity = ((capacity as f64 * self.small_ratio) as usize).max(1);
+        self.ghost_capacity = capacity.saturating_sub(self.small_capacity);
+        while self.ghost.len() > self.ghost_capacity && self.ghost.pop_back().is_some() {}
+    }
+
+    fn before_insert(&mut self, hash: u64) {
+        self.incoming_main = self.ghost.remove(hash);
+    }
+
+    fn on_insert(&mut self, slot: usize, _hash: u64) {
+        if slot >= self.in_main.len() {
+            self.in_main.resize(slot + 1, false);
+            self.frequencies.resize(slot + 1, 0);
+        }
+        let main = std::mem::take(&mut self.incoming_main);
+        self.in_main[slot] = main;
+        self.frequencies[slot] = 0;
+        if main {
+            self.main_len += 1;
+            self.links.push_front(&mut self.main, slot);
+        } else {
+            self.small_len += 1;
+            self.links.push_front(&mut self.small, slot);
+        }
+    }
+
+    fn on_hit(&mut self, slot: usize) {
+        let frequency = &mut self.frequencies[slot];
+        *frequency = (*frequency + 1).min(MAX_FREQUENCY);
+    }
+
+    fn on_remove(&mut self, slot: usize) {
+        if self.in_main[slot] {
+            self.main_len -= 1;
+            self.links.unlink(&mut self.main, slot);
+        } else {
+            self.small_len -= 1;
+            self.links.unlink(&mut self.small, slot);
+        }
+    }
+
+    fn on_evict(&mut self, slot: usize, hash: u64) {
+        let from_small = !self.in_main[slot];
+        self.on_remove(slot);
+        if from_small && self.ghost_capacity > 0 {
+            self.ghost.push_front(hash);
+            if self.ghost.len() > self.ghost_capacity {
+                self.ghost.pop_back();
+            }
+        }
+    }
+
+    fn victim(&mut self) -> Option<usize> {
+        loop {
+            if self.small_len > 0 && (self.small_len >= self.small_capacity || self.main_len == 0) {
+                let slot = self.small.tail()?;
+                if self.frequencies[slot] == 0 {
+                    return Some(slot);
+                }
+                self.links.unlink(&mut self.small, slot);
+                self.small_len -= 1;
+                self.links.push_front(&mut self.main, slot);
+                self.main_len += 1;
+                self.in_main[slot] = true;
+                self.frequencies[slot] = 0;
+            } else {
+                let slot = self.main.tail()?;
+                if self.frequencies[slot] == 0 {
+                    return Some(slot);
+                }
+                self.frequencies[slot] -= 1;
+                self.links.move_to_front(&mut self.main, slot);
+            }
+        }
+    }
+
+    fn first(&self) -> Option<usize> {
+        self.main.head().or(self.small.head())
+    }
+
+    fn last(&self) -> Option<usize> {
+        self.small.tail().or(self.main.tail())
+    }
+
+    fn next(&self, slot: usize) -> Option<usize> {
+        match self.links.next(slot) {
+            None if self.in_main[slot] => self.small.head(),
+            next => next,
+        }
+    }
+
+    fn prev(&self, slot: usize) -> Option<usize> {
+        match self.links.prev(slot) {
+            None if !self.in_main[slot] => self.main.tail(),
+            prev => prev,
+        }
+    }
+
+    fn relocate(&mut self, from: usize, to: usize) {
+        if to >= self.in_main.len() {
+            self.in_main.resize(to + 1, false);
+            self.frequencies.resize(to + 1, 0);
+        }
+        self.in_main[to] = self.in_main[from];
+        self.frequencies[to] = self.frequencies[from];
+        let mut list = *self.list(from);
+        self.links.relocate(&mut list, from, to);
+        *self.list(to) = list;
+    }
+
+    fn clear(&mut self) {
+        self.links.clear();
+        self.small = List::default();
+        self.main = List::default();
+        self.small_len = 0;
+        self.main_len = 0;
+        self.in_main.clear();
+        self.frequencies.clear();
+        self.ghost.clear();
+    }
+
+    fn shrink_to(&mut self, slots: usize) {
+        self.links.truncate(slots);
+        self.in_main.truncate(slots);
+        self.in_main.shrink_to_fit();
+        self.frequencies.truncate(slots);
+        self.frequencies.shrink_to_fit();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::policy::assert_scan_resistant;
+    use crate::Displaced;
+
+    #[test]
+    fn test_one_hit_wonders_never_reach_main() {
+        let s3_fifo = assert_scan_resistant(S3FifoCache::new(20), 10, 20, 5, 1_000);
+        // Every scanned key left through the small queue.
+        assert_eq!(s3_fifo.policy().main_len(), 10);
+        assert_eq!(s3_fifo.policy().ghost_len(), 18);
+    }
+
+    #[test]
+    fn test_hits_and_ghosts_lead_to_main() {
+        let mut cache = Cache::with_policy(4, S3Fifo::with_small_ratio(0.25));
+        for key in 1..=4 {
+            cache.put(key, ());
+        }
+        cache.get(&1);
+        // 1 was hit while small, so it moves to main and 2 goes instead.
+        assert_eq!(cache.put(5, ()), [Displaced::Evicted(2, ())]);
+        assert_eq!(cache.keys().copied().collect::<Vec<_>>(), [1, 5, 4, 3]);
+        assert_eq!(cache.put(6, ()), [Displaced::Evicted(3, ())]);
+
+        // 2 is still remembered by the ghost queue.
+        assert_eq!(cache.put(2, ()), [Displaced::Evicted(4, ())]);
+        let s3_fifo = cache.policy();
+        assert_eq!((s3_fifo.main_len(), s3_fifo.small_len()), (2, 2));
+        assert_eq!(s3_fifo.ghost_len(), 2);
+        assert_eq!(
+            cache.keys().rev().copied().collect::<Vec<_>>(),
+            [5, 6, 1, 2]
+        );
+    }
+
+    #[test]
+    fn test_main_gives_hit_entries_another_lap() {
+        let mut cache = Cache::with_policy(2, S3Fifo::with_small_ratio(0.5));
+        cache.put(1, ());
+        cache.get(&1);
+        cache.put(2, ());
+        cache.get(&2);
+        // Both move to main, where 1 is oldest with no hits left.
+        assert_eq!(cache.put(3, ()), [Displaced::Evicted(1, ())]);
+        cache.get(&2);
+        // The small queue is at its share: 3 goes.
+        assert_eq!(cache.put(4, ()), [Displaced::Evicted(3, ())]);
+        assert_eq!(cache.policy().main_len(), 1);
+    }
+}
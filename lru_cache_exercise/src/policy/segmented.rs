@@ -0,0 +1,255 @@
+use super::Policy;
+use crate::cache::Cache;
+use crate::list::{Links, List};
+
+/// A [`Cache`] using Segmented LRU.
+pub type SegmentedLruCache<K, V> = Cache<K, V, Segmented>;
+
+/// Segmented LRU (SLRU): a probationary and a protected LRU segment.
+///
+/// New keys enter the probationary segment. A hit there promotes the entry to
+/// the protected segment, which holds a configurable share of the capacity.
+/// When the protected segment overflows, its least recently used entry drops
+/// back to the front of the probationary segment. The victim is the least
+/// recently used probationary entry.
+///
+/// Keys used once never leave the probationary segment, so they compete only
+/// with each other. Iteration runs through the protected segment and then the
+/// probationary one, each from most to least recently used.
+///
+/// ```
+/// use lru_cache_exercise::SegmentedLruCache;
+///
+/// let mut cache = SegmentedLruCache::new(4);
+/// cache.put("hot", 1);
+/// cache.get("hot");
+/// for key in ["a", "b", "c", "d", "e"] {
+///     cache.put(key, 0);
+/// }
+/// assert!(cache.contains_key("hot"));
+/// assert_eq!(cache.policy().protected_len(), 1);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Segmented {
+    links: Links,
+    probation: List,
+    protected: List,
+    probation_len: usize,
+    protected_len: usize,
+    /// Whether each tracked slot is protected, indexed by slot.
+    is_protected: Vec<bool>,
+    protected_ratio: f64,
+    protected_capacity: usize,
+}
+
+impl Segmented {
+    /// Creates a policy whose protected segment holds 80% of the capacity.
+    pub fn new() -> Self {
+        Self::with_protected_ratio(0.8)
+    }
+
+    /// Creates a policy whose protected segment holds `ratio` of the
+    /// capacity.
+    ///
+    /// # Panics
+    ///
+    /// Panics unless `ratio` is between 0 and 1.
+    pub fn with_protected_ratio(ratio: f64) -> Self {
+        assert!(
+            (0.0..=1.0).contains(&ratio),
+            "the protected ratio must be between 0 and 1"
+        );
+        Self {
+            links: Links::default(),
+            probation: List::default(),
+            protected: List::default(),
+            probation_len: 0,
+            protected_len: 0,
+            is_protected: Vec::new(),
+            protected_ratio: ratio,
+            protected_capacity: 0,
+        }
+    }
+
+    /// Returns the number of entries in the probationary segment.
+    pub fn probation_len(&self) -> usize {
+        self.probation_len
+    }
+
+    /// Returns the number of entries in the protected segment.
+    pub fn protected_len(&self) -> usize {
+        self.protected_len
+    }
+
+    fn list(&mut self, slot: usize) -> &mut List {
+        if self.is_protected[slot] {
+            &mut self.protected
+        } else {
+            &mut self.probation
+        }
+    }
+
+    /// Moves least recently used protected entries back to probation until
+    /// the protected segment fits its share.
+    fn demote_overflow(&mut self) {
+        while self.protected_len > self.protected_capacity {
+            let Some(slot) = self.protected.tail() else {
+                break;
+            };
+            self.links.unlink(&mut self.protected, slot);
+            self.protected_len -= 1;
+            self.is_protected[slot] = false;
+            self.links.push_front(&mut self.probation, slot);
+            self.probation_len += 1;
+        }
+    }
+}
+
+impl Default for Segmented {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Policy for Segmented {
+    fn set_capacity(&mut self, capacity: usize) {
+        self.protected_capacity = (capacity as f64 * self.protected_ratio) as usize;
+        self.demote_overflow();
+    }
+
+    fn on_insert(&mut self, slot: usize, _hash: u64) {
+        if slot >= self.is_protected.len() {
+            self.is_protected.resize(slot + 1, false);
+        }
+        self.is_protected[slot] = false;
+        self.links.push_front(&mut self.probation, slot);
+        self.probation_len += 1;
+    }
+
+    fn on_hit(&mut self, slot: usize) {
+        if self.is_protected[slot] {
+            self.links.move_to_front(&mut self.protected, slot);
+            return;
+        }
+        if self.protected_capacity == 0 {
+            self.links.move_to_front(&mut self.probation, slot);
+            return;
+        }
+        self.links.unlink(&mut self.probation, slot);
+        self.probation_len -= 1;
+        self.is_protected[slot] = true;
+        self.links.push_front(&mut self.protected, slot);
+        self.protected_len += 1;
+        self.demote_overflow();
+    }
+
+    fn on_remove(&mut self, slot: usize) {
+        if self.is_protected[slot] {
+            self.protected_len -= 1;
+            self.links.unlink(&mut self.protected, slot);
+        } else {
+            self.probation_len -= 1;
+            self.links.unlink(&mut self.probation, slot);
+        }
+    }
+
+    fn victim(&mut self) -> Option<usize> {
+        self.probation.tail().or(self.protected.tail())
+    }
+
+    fn first(&self) -> Option<usize> {
+        self.protected.head().or(self.probation.head())
+    }
+
+    fn last(&self) -> Option<usize> {
+        self.probation.tail().or(self.protected.tail())
+    }
+
+    fn next(&self, slot: usize) -> Option<usize> {
+        match self.links.next(slot) {
+            None if self.is_protected[slot] => self.probation.head(),
+            next => next,
+        }
+    }
+
+    fn prev(&self, slot: usize) -> Option<usize> {
+        match self.links.prev(slot) {
+            None if !self.is_protected[slot] => self.protected.tail(),
+            prev => prev,
+        }
+    }
+
+    fn relocate(&mut self, from: usize, to: usize) {
+        if to >= self.is_protected.len() {
+            self.is_protected.resize(to + 1, false);
+        }
+        self.is_protected[to] = self.is_protected[from];
+        let mut list = *self.list(from);
+        self.links.relocate(&mut list, from, to);
+        *self.list(to) = list;
+    }
+
+    fn clear(&mut self) {
+        self.links.clear();
+        self.probation = List::default();
+        self.protected = List::default();
+        self.probation_len = 0;
+        self.protected_len = 0;
+        self.is_protected.clear();
+    }
+
+    fn shrink_to(&mut self, slots: usize) {
+        self.links.truncate(slots);
+        self.is_protected.truncate(slots);
+        self.is_protected.shrink_to_fit();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::policy::assert_scan_resistant;
+    use crate::Displaced;
+
+    #[test]
+    fn test_one_hit_wonders_do_not_displace_reused_keys() {
+        let slru = assert_scan_resistant(SegmentedLruCache::new(10), 6, 3, 3, 1_000);
+        assert_eq!(slru.policy().protected_len(), 6);
+    }
+
+    #[test]
+    fn test_protected_overflow_is_demoted() {
+        let mut cache = Cache::with_policy(4, Segmented::with_protected_ratio(0.5));
+        for key in 1..=4 {
+            cache.put(key, ());
+        }
+        for key in [1, 2, 3] {
+            cache.get(&key);
+        }
+        // 1 fell back to probation when 3 was promoted.
+        assert_eq!(cache.keys().copied().collect::<Vec<_>>(), [3, 2, 1, 4]);
+        assert_eq!(cache.put(5, ()), [Displaced::Evicted(4, ())]);
+        assert_eq!(cache.put(6, ()), [Displaced::Evicted(1, ())]);
+        assert_eq!(
+            (
+                cache.policy().protected_len(),
+                cache.policy().probation_len()
+            ),
+            (2, 2)
+        );
+    }
+
+    #[test]
+    fn test_resize_shrinks_protected_segment() {
+        let mut cache = SegmentedLruCache::new(10);
+        for key in 0..10 {
+            cache.put(key, ());
+            cache.get(&key);
+        }
+        assert_eq!(cache.policy().protected_len(), 8);
+        cache.resize(5);
+        assert_eq!(cache.len(), 5);
+        assert_eq!(cache.policy().protected_len(), 4);
+        assert!((5..10).all(|key| cache.contains_key(&key)));
+    }
+}
@@ -0,0 +1,402 @@
+use std::borrow::Borrow;
+use std::hash::Hash;
+
+use super::Policy;
+use crate::cache::Cache;
+use crate::list::{Links, List};
+use crate::sketch::{CountMinSketch, Doorkeeper};
+
+/// A [`Cache`] using W-TinyLFU.
+pub type TinyLfuCache<K, V> = Cache<K, V, TinyLfu>;
+
+/// The regions of a [`TinyLfu`] policy, in iteration order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Region {
+    Protected,
+    Probation,
+    Window,
+}
+
+impl Region {
+    const ALL: [Region; 3] = [Region::Protected, Region::Probation, Region::Window];
+}
+
+/// Window TinyLFU (W-TinyLFU), after Einziger, Friedman and Manes.
+///
+/// New keys enter a small LRU window, by default 1% of the capacity. Keys
+/// pushed out of the window are candidates for the main region, a
+/// [segmented LRU](super::Segmented) with 80% of its space protected. Once
+/// the cache is full, a candidate is only admitted if the TinyLFU filter
+/// estimates it has been used more often than the main region's victim;
+/// otherwise the candidate itself is evicted.
+///
+/// The filter counts every insertion of a new key and every hit. Its first
+/// sighting of a key only sets a [`Doorkeeper`] bloom filter, and later
+/// ones count in a [`CountMinSketch`] that halves its counters periodically,
+/// clearing the doorkeeper as it does. The sketch is sized for the expected
+/// number of entries, by default the cache's capacity, and the doorkeeper for
+/// one sample of the sketch. Both start over only when that number changes,
+/// so with [`with_expected_entries`](Self::with_expected_entries) a resize
+/// keeps the frequency history.
+///
+/// Iteration runs through the protected and probationary segments of the
+/// main region and then the window, each from most to least recently used.
+///
+/// ```
+/// use lru_cache_exercise::TinyLfuCache;
+///
+/// let mut cache = TinyLfuCache::new(100);
+/// for _ in 0..3 {
+///     for key in 0..50 {
+///         if cache.get(&key).is_none() {
+///             cache.put(key, ());
+///         }
+///     }
+/// }
+/// for key in 1_000..3_000 {
+///     cache.put(key, ());
+/// }
+/// assert!((0..50).all(|key| cache.contains_key(&key)));
+/// ```
+#[derive(Debug, Clone)]
+pub struct TinyLfu {
+    links: Links,
+    /// The list of each region, indexed by `Region as usize`.
+    lists: [List; 3],
+    lens: [usize; 3],
+    /// The region of each tracked slot, indexed by slot.
+    regions: Vec<Region>,
+    /// The key hash of each tracked slot, indexed by slot.
+    hashes: Vec<u64>,
+    sketch: CountMinSketch,
+    doorkeeper: Doorkeeper,
+    window_ratio: f64,
+    /// The number of entries to size the filter for, if not the capacity.
+    expected_entries: Option<usize>,
+    /// The number of entries the filter is sized for.
+    sketch_entries: usize,
+    window_capacity: usize,
+    protected_capacity: usize,
+}
+
+impl TinyLfu {
+    /// Creates a policy whose window holds 1% of the capacity.
+    pub fn new() -> Self {
+        Self::with_window_ratio(0.01)
+    }
+
+    /// Creates a policy whose window holds `ratio` of the capacity, and at
+    /// least one entry.
+    ///
+    /// # Panics
+    ///
+    /// Panics unless `ratio` is between 0 and 1.
+    pub fn with_window_ratio(ratio: f64) -> Self {
+        assert!(
+            (0.0..=1.0).contains(&ratio),
+            "the window ratio must be between 0 and 1"
+        );
+        Self {
+            links: Links::default(),
+            lists: [List::default(); 3],
+            lens: [0; 3],
+            regions: Vec::new(),
+            hashes: Vec::new(),
+            sketch: CountMinSketch::new(0),
+            doorkeeper: Doorkeeper::new(0),
+            window_ratio: ratio,
+            expected_entries: None,
+            sketch_entries: 0,
+            window_capacity: 1,
+            protected_capacity: 0,
+        }
+    }
+
+    /// Sizes the filter for about `entries` distinct keys instead of the
+    /// capacity. Use this when the cache has a weigher, since its capacity
+    /// is then a total weight rather than a number of entries.
+    pub fn with_expected_entries(mut self, entries: usize) -> Self {
+        self.expected_entries = Some(entries);
+        self
+    }
+
+    /// Returns the number of entries in the window.
+    pub fn window_len(&self) -> usize {
+        self.lens[Region::Window as usize]
+    }
+
+    /// Returns the number of entries in the main region's probationary
+    /// segment.
+    pub fn probation_len(&self) -> usize {
+        self.lens[Region::Probation as usize]
+    }
+
+    /// Returns the number of entries in the main region's protected segment.
+    pub fn protected_len(&self) -> usize {
+        self.lens[Region::Protected as usize]
+    }
+
+    /// Returns the filter's estimate of how often the key hashing to `hash`
+    /// was used recently.
+    fn estimate(&self, hash: u64) -> u8 {
+        let estimate = self.sketch.estimate(hash);
+        estimate.saturating_add(self.doorkeeper.contains(hash) as u8)
+    }
+
+    /// Counts a use of the key hashing to `hash`.
+    fn record(&mut self, hash: u64) {
+        if self.doorkeeper.insert(hash) {
+            return;
+        }
+        if self.sketch.increment(hash) {
+            self.doorkeeper.clear();
+        }
+    }
+
+    fn push_front(&mut self, slot: usize, region: Region) {
+        self.regions[slot] = region;
+        self.lens[region as usize] += 1;
+        self.links
+            .push_front(&mut self.lists[region as usize], slot);
+    }
+
+    fn unlink(&mut self, slot: usize) {
+        let region = self.regions[slot] as usize;
+        self.lens[region] -= 1;
+        self.links.unlink(&mut self.lists[region], slot);
+    }
+
+    fn tail(&self, region: Region) -> Option<usize> {
+        self.lists[region as usize].tail()
+    }
+
+    /// Moves overflow from the window and the protected segment to the
+    /// front of the probationary segment.
+    fn rebalance(&mut self) {
+        while self.window_len() > self.window_capacity {
+            let Some(slot) = self.tail(Region::Window) else {
+                break;
+            };
+            self.unlink(slot);
+            self.push_front(slot, Region::Probation);
+        }
+        while self.protected_len() > self.protected_capacity {
+            let Some(slot) = self.tail(Region::Protected) else {
+                break;
+            };
+            self.unlink(slot);
+            self.push_front(slot, Region::Probation);
+        }
+    }
+}
+
+impl Default for TinyLfu {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Policy for TinyLfu {
+    fn set_capacity(&mut self, capacity: usize) {
+        let entries = self
+            .expected_entries
+            .unwrap_or(capacity)
+            .min(CountMinSketch::MAX_CAPACITY);
+        if entries != self.sketch_entries {
+            self.sketch = CountMinSketch::new(entries);
+            self.doorkeeper = Doorkeeper::new(self.sketch.sample_size());
+            self.sketch_entries = entries;
+        }
+        self.window_capacity = ((capacity as f64 * self.window_ratio) as usize).max(1);
+        let main = capacity.saturating_sub(self.window_capacity);
+        self.protected_capacity = (main as f64 * 0.8) as usize;
+        self.rebalance();
+    }
+
+    fn before_insert(&mut self, hash: u64) {
+        self.record(hash);
+    }
+
+    fn on_insert(&mut self, slot: usize, hash: u64) {
+        if slot >= self.regions.len() {
+            self.regions.resize(slot + 1, Region::Window);
+            self.hashes.resize(slot + 1, 0);
+        }
+        self.hashes[slot] = hash;
+        self.push_front(slot, Region::Window);
+        self.rebalance();
+    }
+
+    fn on_hit(&mut self, slot: usize) {
+        self.record(self.hashes[slot]);
+        match self.regions[slot] {
+            Region::Probation if self.protected_capacity > 0 => {
+                self.unlink(slot);
+                self.push_front(slot, Region::Protected);
+                self.rebalance();
+            }
+            region => {
+                let list = &mut self.lists[region as usize];
+                self.links.move_to_front(list, slot);
+            }
+        }
+    }
+
+    fn on_remove(&mut self, slot: usize) {
+        self.unlink(slot);
+    }
+
+    fn victim(&mut self) -> Option<usize> {
+        let candidate = self
+            .tail(Region::Window)
+            .filter(|_| self.window_len() >= self.window_capacity);
+        let victim = self
+            .tail(Region::Probation)
+            .or(self.tail(Region::Protected));
+        match (candidate, victim) {
+            (Some(candidate), Some(victim)) => {
+                if self.estimate(self.hashes[candidate]) > self.estimate(self.hashes[victim]) {
+                    self.unlink(candidate);
+                    self.push_front(candidate, Region::Probation);
+                    Some(victim)
+                } else {
+                    Some(candidate)
+                }
+            }
+            (candidate, victim) => victim.or(candidate).or(self.tail(Region::Window)),
+        }
+    }
+
+    fn first(&self) -> Option<usize> {
+        Region::ALL
+            .iter()
+            .find_map(|&region| self.lists[region as usize].head())
+    }
+
+    fn last(&self) -> Option<usize> {
+        Region::ALL
+            .iter()
+            .rev()
+            .find_map(|&region| self.tail(region))
+    }
+
+    fn next(&self, slot: usize) -> Option<usize> {
+        self.links.next(slot).or_else(|| {
+            let region = self.regions[slot] as usize;
+            Region::ALL[region + 1..]
+                .iter()
+                .find_map(|&region| self.lists[region as usize].head())
+        })
+    }
+
+    fn prev(&self, slot: usize) -> Option<usize> {
+        self.links.prev(slot).or_else(|| {
+            let region = self.regions[slot] as usize;
+            Region::ALL[..region]
+                .iter()
+                .rev()
+                .find_map(|&region| self.tail(region))
+        })
+    }
+
+    fn relocate(&mut self, from: usize, to: usize) {
+        if to >= self.regions.len() {
+            self.regions.resize(to + 1, Region::Window);
+            self.hashes.resize(to + 1, 0);
+        }
+        self.regions[to] = self.regions[from];
+        self.hashes[to] = self.hashes[from];
+        let list = &mut self.lists[self.regions[from] as usize];
+        self.links.relocate(list, from, to);
+    }
+
+    fn clear(&mut self) {
+        self.links.clear();
+        self.lists = [List::default(); 3];
+        self.lens = [0; 3];
+        self.regions.clear();
+        self.hashes.clear();
+        self.sketch.clear();
+        self.doorkeeper.clear();
+    }
+
+    fn shrink_to(&mut self, slots: usize) {
+        self.links.truncate(slots);
+        self.regions.truncate(slots);
+        self.regions.shrink_to_fit();
+        self.hashes.truncate(slots);
+        self.hashes.shrink_to_fit();
+    }
+}
+
+impl<K: Hash + Eq, V> Cache<K, V, TinyLfu> {
+    /// Returns the admission filter's estimate of how often `key` was used
+    /// recently, whether or not the cache holds it.
+    pub fn frequency<Q>(&self, key: &Q) -> u8
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.policy().estimate(self.hash_of(key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::policy::assert_scan_resistant;
+    use crate::Displaced;
+
+    #[test]
+    fn test_one_hit_wonders_are_not_admitted() {
+        let tiny_lfu = assert_scan_resistant(TinyLfuCache::new(100), 80, 4, 0, 2_000);
+        // Only the window admits scanned keys.
+        assert_eq!(tiny_lfu.policy().window_len(), 1);
+    }
+
+    #[test]
+    fn test_frequent_candidate_displaces_main_victim() {
+        let mut cache = Cache::with_policy(4, TinyLfu::with_window_ratio(0.25));
+        for key in 1..=4 {
+            cache.put(key, ());
+        }
+        // 1, 2 and 3 spilled into probation; 4 is in the window.
+        assert_eq!(cache.policy().probation_len(), 3);
+        cache.get(&4);
+        cache.get(&4);
+        assert_eq!(cache.put(5, ()), [Displaced::Evicted(1, ())]);
+        assert_eq!(cache.policy().window_len(), 1);
+        // 5 was seen once, no more than the probation victim 2.
+        assert_eq!(cache.put(6, ()), [Displaced::Evicted(5, ())]);
+        assert_eq!(cache.frequency(&4), 3);
+    }
+
+    #[test]
+    fn test_expected_entries_survive_resize() {
+        let policy = TinyLfu::new().with_expected_entries(100);
+        let mut cache = Cache::with_policy(1 << 20, policy).with_weigher(|_: &u32, _: &()| 1 << 10);
+        cache.put(1, ());
+        cache.get(&1);
+        cache.get(&1);
+        cache.resize(1 << 19);
+        assert_eq!(cache.frequency(&1), 3);
+        assert_eq!(cache.policy().sketch_entries, 100);
+    }
+
+    #[test]
+    fn test_iterates_main_then_window() {
+        let mut cache = Cache::with_policy(10, TinyLfu::with_window_ratio(0.2));
+        for key in 1..=5 {
+            cache.put(key, ());
+        }
+        cache.get(&1);
+        assert_eq!(cache.keys().copied().collect::<Vec<_>>(), [1, 3, 2, 5, 4]);
+        assert_eq!(
+            cache.keys().rev().copied().collect::<Vec<_>>(),
+            [4, 5, 2, 3, 1]
+        );
+        cache.remove(&2);
+        cache.shrink_to_fit();
+        assert_eq!(cache.keys().copied().collect::<Vec<_>>(), [1, 3, 5, 4]);
+    }
+}
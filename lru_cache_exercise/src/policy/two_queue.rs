@@ -0,0 +1,267 @@
+use super::Policy;
+use crate::cache::Cache;
+use crate::ghost::GhostQueue;
+use crate::list::{Links, List};
+
+/// A [`Cache`] using the 2Q policy.
+pub type TwoQueueCache<K, V> = Cache<K, V, TwoQueue>;
+
+/// The full 2Q policy, after Johnson and Shasha.
+///
+/// New keys enter A1in, a FIFO queue that reads do not reorder. Keys evicted
+/// from A1in are remembered, by hash only, in the ghost queue A1out. A key
+/// that comes back while it is in A1out has proved it is reused and enters
+/// Am, an LRU list holding the rest of the cache. The victim is the oldest
+/// entry of A1in while A1in is over its share of the capacity, and the least
+/// recently used entry of Am otherwise.
+///
+/// Keys used only once pass through A1in and A1out without touching Am. The
+/// queue sizes are fractions of the capacity: by default A1in gets a quarter
+/// and A1out remembers half as many keys as the cache holds. Iteration runs
+/// through Am from most to least recently used, then A1in from newest to
+/// oldest.
+///
+/// ```
+/// use lru_cache_exercise::TwoQueueCache;
+///
+/// let mut cache = TwoQueueCache::new(2);
+/// cache.put("a", 1);
+/// cache.put("b", 2);
+/// cache.put("c", 3);
+/// // "a" was pushed out of A1in but is remembered in A1out...
+/// assert!(!cache.contains_key("a"));
+/// // ...so coming back moves it straight to Am.
+/// cache.put("a", 1);
+/// assert_eq!(cache.policy().main_len(), 1);
+/// ```
+#[derive(Debug, Clone)]
+pub struct TwoQueue {
+    links: Links,
+    a1in: List,
+    am: List,
+    a1in_len: usize,
+    am_len: usize,
+    /// Whether each tracked slot is in Am rather than A1in, indexed by slot.
+    in_am: Vec<bool>,
+    a1out: GhostQueue,
+    /// Whether the key being inserted was found in A1out. Checked before the
+    /// cache evicts to make room, so that the eviction cannot push the key's
+    /// own ghost out.
+    incoming_reused: bool,
+    in_ratio: f64,
+    out_ratio: f64,
+    in_capacity: usize,
+    out_capacity: usize,
+}
+
+impl TwoQueue {
+    /// Creates a policy that gives A1in a quarter of the capacity and
+    /// remembers half a capacity's worth of keys in A1out.
+    pub fn new() -> Self {
+        Self::with_ratios(0.25, 0.5)
+    }
+
+    /// Creates a policy that gives A1in `in_ratio` of the capacity and
+    /// remembers `out_ratio` times the capacity in keys in A1out.
+    ///
+    /// # Panics
+    ///
+    /// Panics unless `in_ratio` is between 0 and 1 and `out_ratio` is
+    /// non-negative.
+    pub fn with_ratios(in_ratio: f64, out_ratio: f64) -> Self {
+        assert!(
+            (0.0..=1.0).contains(&in_ratio),
+            "the A1in ratio must be between 0 and 1"
+        );
+        assert!(out_ratio >= 0.0, "the A1out ratio must not be negative");
+        Self {
+            links: Links::default(),
+            a1in: List::default(),
+            am: List::default(),
+            a1in_len: 0,
+            am_len: 0,
+            in_am: Vec::new(),
+            a1out: GhostQueue::default(),
+            incoming_reused: false,
+            in_ratio,
+            out_ratio,
+            in_capacity: 0,
+            out_capacity: 0,
+        }
+    }
+
+    /// Returns the number of entries in A1in, seen once.
+    pub fn recent_len(&self) -> usize {
+        self.a1in_len
+    }
+
+    /// Returns the number of entries in Am, seen again after leaving A1in.
+    pub fn main_len(&self) -> usize {
+        self.am_len
+    }
+
+    /// Returns the number of keys remembered in A1out.
+    pub fn ghost_len(&self) -> usize {
+        self.a1out.len()
+    }
+
+    fn list(&mut self, slot: usize) -> &mut List {
+        if self.in_am[slot] {
+            &mut self.am
+        } else {
+            &mut self.a1in
+        }
+    }
+}
+
+impl Default for TwoQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Policy for TwoQueue {
+    fn set_capacity(&mut self, capacity: usize) {
+        self.in_capacity = ((capacity as f64 * self.in_ratio) as usize).max(1);
+        self.out_capacity = (capacity as f64 * self.out_ratio) as usize;
+        while self.a1out.len() > self.out_capacity && self.a1out.pop_back().is_some() {}
+    }
+
+    fn before_insert(&mut self, hash: u64) {
+        self.incoming_reused = self.a1out.remove(hash);
+    }
+
+    fn on_insert(&mut self, slot: usize, _hash: u64) {
+        if slot >= self.in_am.len() {
+            self.in_am.resize(slot + 1, false);
+        }
+        let reused = std::mem::take(&mut self.incoming_reused);
+        self.in_am[slot] = reused;
+        if reused {
+            self.am_len += 1;
+            self.links.push_front(&mut self.am, slot);
+        } else {
+            self.a1in_len += 1;
+            self.links.push_front(&mut self.a1in, slot);
+        }
+    }
+
+    fn on_hit(&mut self, slot: usize) {
+        if self.in_am[slot] {
+            self.links.move_to_front(&mut self.am, slot);
+        }
+    }
+
+    fn on_remove(&mut self, slot: usize) {
+        if self.in_am[slot] {
+            self.am_len -= 1;
+            self.links.unlink(&mut self.am, slot);
+        } else {
+            self.a1in_len -= 1;
+            self.links.unlink(&mut self.a1in, slot);
+        }
+    }
+
+    fn on_evict(&mut self, slot: usize, hash: u64) {
+        let from_a1in = !self.in_am[slot];
+        self.on_remove(slot);
+        if from_a1in && self.out_capacity > 0 {
+            self.a1out.push_front(hash);
+            if self.a1out.len() > self.out_capacity {
+                self.a1out.pop_back();
+            }
+        }
+    }
+
+    fn victim(&mut self) -> Option<usize> {
+        if self.a1in_len > self.in_capacity || self.am_len == 0 {
+            self.a1in.tail()
+        } else {
+            self.am.tail()
+        }
+    }
+
+    fn first(&self) -> Option<usize> {
+        self.am.head().or(self.a1in.head())
+    }
+
+    fn last(&self) -> Option<usize> {
+        self.a1in.tail().or(self.am.tail())
+    }
+
+    fn next(&self, slot: usize) -> Option<usize> {
+        match self.links.next(slot) {
+            None if self.in_am[slot] => self.a1in.head(),
+            next => next,
+        }
+    }
+
+    fn prev(&self, slot: usize) -> Option<usize> {
+        match self.links.prev(slot) {
+            None if !self.in_am[slot] => self.am.tail(),
+            prev => prev,
+        }
+    }
+
+    fn relocate(&mut self, from: usize, to: usize) {
+        if to >= self.in_am.len() {
+            self.in_am.resize(to + 1, false);
+        }
+        self.in_am[to] = self.in_am[from];
+        let mut list = *self.list(from);
+        self.links.relocate(&mut list, from, to);
+        *self.list(to) = list;
+    }
+
+    fn clear(&mut self) {
+        self.links.clear();
+        self.a1in = List::default();
+        self.am = List::default();
+        self.a1in_len = 0;
+        self.am_len = 0;
+        self.in_am.clear();
+        self.a1out.clear();
+    }
+
+    fn shrink_to(&mut self, slots: usize) {
+        self.links.truncate(slots);
+        self.in_am.truncate(slots);
+        self.in_am.shrink_to_fit();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::policy::assert_scan_resistant;
+
+    #[test]
+    fn test_one_hit_wonders_do_not_displace_reused_keys() {
+        let two_queue = assert_scan_resistant(TwoQueueCache::new(16), 8, 20, 4, 1_000);
+        assert_eq!(two_queue.policy().main_len(), 8);
+    }
+
+    #[test]
+    fn test_reads_in_a1in_do_not_promote() {
+        let mut cache = TwoQueueCache::new(4);
+        cache.put(1, ());
+        cache.get(&1);
+        cache.get(&1);
+        assert_eq!(cache.policy().recent_len(), 1);
+        assert_eq!(cache.policy().main_len(), 0);
+    }
+
+    #[test]
+    fn test_a1out_is_bounded() {
+        let mut cache = Cache::with_policy(8, TwoQueue::with_ratios(0.25, 0.5));
+        for key in 0..100 {
+            cache.put(key, ());
+        }
+        assert_eq!(cache.policy().ghost_len(), 4);
+        // Only the last four evicted keys are remembered.
+        cache.put(91, ());
+        cache.put(80, ());
+        assert_eq!(cache.policy().main_len(), 1);
+        assert_eq!(cache.keys().next(), Some(&91));
+    }
+}
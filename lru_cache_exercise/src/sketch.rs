@@ -0,0 +1,245 @@
+//! Compact, approximate frequency counters over key hashes.
+
+/// Multipliers that derive one independent-looking index per row from a
+/// single 64-bit hash.
+const ROW_SEEDS: [u64; 4] = [
+    0x9e37_79b9_7f4a_7c15,
+    0xbf58_476d_1ce4_e5b9,
+    0x94d0_49bb_1331_11eb,
+    0xd6e8_feb8_6659_fd93,
+];
+
+/// Every counter in a word with its high bit cleared, for halving sixteen
+/// counters at once.
+const HALF_MASK: u64 = 0x7777_7777_7777_7777;
+
+/// A count-min sketch of 4-bit counters that ages its counts.
+///
+/// Estimates how many times each hash was added, using four rows of
+/// counters and reporting the smallest of the four a hash maps to. An
+/// estimate can exceed the true count when hashes collide, but never falls
+/// short of it, up to the counters' maximum of 15. Sixteen counters are
+/// packed into each word, so the sketch costs half a byte per counter.
+///
+/// After a sample of ten additions per hash it was sized for, every counter
+/// is halved. Estimates therefore reflect recent popularity rather than all
+/// time, and keys that were hot long ago fade.
+///
+/// ```
+/// use lru_cache_exercise::CountMinSketch;
+///
+/// let mut sketch = CountMinSketch::new(64);
+/// for _ in 0..3 {
+///     sketch.increment(42);
+/// }
+/// assert_eq!(sketch.estimate(42), 3);
+/// sketch.halve();
+/// assert_eq!(sketch.estimate(42), 1);
+/// ```
+#[derive(Debug, Clone)]
+pub struct CountMinSketch {
+    table: Vec<u64>,
+    /// The number of counters in each row, less one. Rows are a power of two
+    /// counters wide.
+    row_mask: usize,
+    additions: usize,
+    sample_size: usize,
+}
+
+impl CountMinSketch {
+    /// The most distinct hashes a sketch is sized for, which bounds it to
+    /// 32 MiB.
+    pub const MAX_CAPACITY: usize = 1 << 22;
+
+    /// Creates a sketch sized to tell apart the frequencies of about
+    /// `capacity` distinct hashes, with four counters per hash in each row.
+    /// A `capacity` above [`MAX_CAPACITY`](Self::MAX_CAPACITY) is taken as
+    /// that.
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.clamp(4, Self::MAX_CAPACITY);
+        let width = (capacity * 4).next_power_of_two();
+        Self {
+            table: vec![0; 4 * width / 16],
+            row_mask: width - 1,
+            additions: 0,
+            sample_size: capacity * 10,
+        }
+    }
+
+    /// Returns the number of additions after which the counters halve.
+    pub fn sample_size(&self) -> usize {
+        self.sample_size
+    }
+
+    /// Returns the estimated number of times `hash` was added, at most 15.
+    pub fn estimate(&self, hash: u64) -> u8 {
+        (0..ROW_SEEDS.len())
+            .map(|row| {
+                let (word, shift) = self.position(row, hash);
+                ((self.table[word] >> shift) & 0xf) as u8
+            })
+            .min()
+            .unwrap_or(0)
+    }
+
+    /// Adds one to the count of `hash`.
+    ///
+    /// Returns `true` if the addition completed a sample and every counter
+    /// was halved, so that callers can age any state they keep alongside.
+    pub fn increment(&mut self, hash: u64) -> bool {
+        let mut added = false;
+        for row in 0..ROW_SEEDS.len() {
+            let (word, shift) = self.position(row, hash);
+            if (self.table[word] >> shift) & 0xf < 15 {
+                self.table[word] += 1 << shift;
+                added = true;
+            }
+        }
+        if added {
+            self.additions += 1;
+            if self.additions >= self.sample_size {
+                self.halve();
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Halves every counter, rounding down.
+    pub fn halve(&mut self) {
+        for word in &mut self.table {
+            *word = (*word >> 1) & HALF_MASK;
+        }
+        self.additions /= 2;
+    }
+
+    /// Resets every counter to zero.
+    pub fn clear(&mut self) {
+        self.table.fill(0);
+        self.additions = 0;
+    }
+
+    /// Returns the word holding the counter for `hash` in `row`, and the
+    /// counter's bit offset within it.
+    fn position(&self, row: usize, hash: u64) -> (usize, u32) {
+        let mixed = hash.wrapping_add(row as u64).wrapping_mul(ROW_SEEDS[row]);
+        let index = (mixed ^ (mixed >> 32)) as usize & self.row_mask;
+        let words_per_row = (self.row_mask + 1) / 16;
+        (row * words_per_row + index / 16, (index % 16) as u32 * 4)
+    }
+}
+
+/// A bloom filter that remembers which hashes have been seen at all.
+///
+/// Placed in front of a [`CountMinSketch`], it absorbs the first sighting of
+/// every hash so that keys seen only once never take up space in the
+/// sketch. It can answer that a hash was seen when it was not, but never the
+/// reverse. Clear it whenever the sketch halves.
+///
+/// ```
+/// use lru_cache_exercise::Doorkeeper;
+///
+/// let mut doorkeeper = Doorkeeper::new(64);
+/// assert!(doorkeeper.insert(7));
+/// assert!(!doorkeeper.insert(7));
+/// assert!(doorkeeper.contains(7));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Doorkeeper {
+    bits: Vec<u64>,
+    /// The number of bits, less one. The filter is a power of two bits wide.
+    bit_mask: usize,
+}
+
+impl Doorkeeper {
+    /// The most distinct hashes a filter is sized for, which bounds it to
+    /// 32 MiB.
+    pub const MAX_CAPACITY: usize = 1 << 25;
+
+    /// The number of bits set per hash.
+    const PROBES: u64 = 3;
+
+    /// Creates a filter sized for about `capacity` distinct hashes between
+    /// clears, at eight bits per hash. A `capacity` above
+    /// [`MAX_CAPACITY`](Self::MAX_CAPACITY) is taken as that.
+    pub fn new(capacity: usize) -> Self {
+        let bits = (capacity.min(Self::MAX_CAPACITY) * 8)
+            .max(64)
+            .next_power_of_two();
+        Self {
+            bits: vec![0; bits / 64],
+            bit_mask: bits - 1,
+        }
+    }
+
+    /// Returns whether `hash` may have been inserted since the last clear.
+    pub fn contains(&self, hash: u64) -> bool {
+        self.probes(hash)
+            .all(|bit| self.bits[bit / 64] & (1 << (bit % 64)) != 0)
+    }
+
+    /// Records `hash`. Returns `true` if it was not already present.
+    pub fn insert(&mut self, hash: u64) -> bool {
+        let mut inserted = false;
+        for bit in self.probes(hash) {
+            let word = &mut self.bits[bit / 64];
+            inserted |= *word & (1 << (bit % 64)) == 0;
+            *word |= 1 << (bit % 64);
+        }
+        inserted
+    }
+
+    /// Forgets every hash.
+    pub fn clear(&mut self) {
+        self.bits.fill(0);
+    }
+
+    /// Returns the bits for `hash`, by double hashing.
+    fn probes(&self, hash: u64) -> impl Iterator<Item = usize> {
+        let step = (hash >> 32) | 1;
+        let mask = self.bit_mask;
+        (0..Self::PROBES)
+            .map(move |probe| hash.wrapping_add(probe.wrapping_mul(step)) as usize & mask)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sketch_saturates_and_ages() {
+        let mut sketch = CountMinSketch::new(4);
+        for _ in 0..20 {
+            sketch.increment(1);
+        }
+        assert_eq!(sketch.estimate(1), 15);
+        assert_eq!(sketch.estimate(2), 0);
+
+        let sample = sketch.sample_size();
+        assert!((100..).take(sample).any(|hash| sketch.increment(hash)));
+        assert_eq!(sketch.estimate(1), 7);
+    }
+
+    #[test]
+    fn test_size_is_capped() {
+        let sketch = CountMinSketch::new(usize::MAX);
+        assert_eq!(sketch.table.len(), 1 << 22);
+        assert_eq!(sketch.sample_size(), 10 << 22);
+        let doorkeeper = Doorkeeper::new(usize::MAX);
+        assert_eq!(doorkeeper.bits.len(), 1 << 22);
+    }
+
+    #[test]
+    fn test_doorkeeper_clear_forgets() {
+        let mut doorkeeper = Doorkeeper::new(100);
+        for hash in 0..100u64 {
+            doorkeeper.insert(hash.wrapping_mul(0x9e37_79b9_7f4a_7c15));
+        }
+        assert!(
+            (0..100u64).all(|hash| doorkeeper.contains(hash.wrapping_mul(0x9e37_79b9_7f4a_7c15)))
+        );
+        doorkeeper.clear();
+        assert!(!doorkeeper.contains(0x9e37_79b9_7f4a_7c15));
+    }
+}
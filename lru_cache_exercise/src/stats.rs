@@ -0,0 +1,55 @@
+//! Hit, miss and eviction counters.
+
+use std::iter::Sum;
+use std::ops::{Add, AddAssign};
+
+/// Counters describing how well a cache is doing.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Lookups that found a live entry.
+    pub hits: u64,
+    /// Lookups that found nothing, or only an expired entry.
+    pub misses: u64,
+    /// Entries the cache removed on its own, because of capacity or
+    /// expiration. Rejected insertions were never stored and do not count.
+    pub evictions: u64,
+}
+
+impl CacheStats {
+    /// Returns the total number of lookups.
+    pub fn requests(&self) -> u64 {
+        self.hits + self.misses
+    }
+
+    /// Returns the fraction of lookups that hit, or `1.0` if there were none.
+    pub fn hit_rate(&self) -> f64 {
+        match self.requests() {
+            0 => 1.0,
+            requests => self.hits as f64 / requests as f64,
+        }
+    }
+}
+
+impl Add for CacheStats {
+    type Output = CacheStats;
+
+    fn add(self, other: CacheStats) -> CacheStats {
+        CacheStats {
+            hits: self.hits + other.hits,
+            misses: self.misses + other.misses,
+            evictions: self.evictions + other.evictions,
+        }
+    }
+}
+
+impl AddAssign for CacheStats {
+    fn add_assign(&mut self, other: CacheStats) {
+        *self = *self + other;
+    }
+}
+
+impl Sum for CacheStats {
+    fn sum<I: Iterator<Item = CacheStats>>(iter: I) -> CacheStats {
+        iter.fold(CacheStats::default(), Add::add)
+    }
+}
@@ -0,0 +1,57 @@
+//! Per-entry costs for weight-bounded caches.
+
+use std::fmt;
+use std::sync::Arc;
+
+/// Computes how much of a cache's capacity an entry uses.
+///
+/// A cache with a weigher bounds the total weight of its entries instead of
+/// their number. The weight is computed once, when the value is inserted;
+/// changes made through `get_mut` are not re-weighed. Closures taking
+/// `(&K, &V)` and returning `usize` implement this trait.
+pub trait Weigher<K, V> {
+    /// Returns the cost of holding `value` under `key`.
+    fn weigh(&self, key: &K, value: &V) -> usize;
+}
+
+impl<K, V, F> Weigher<K, V> for F
+where
+    F: Fn(&K, &V) -> usize,
+{
+    fn weigh(&self, key: &K, value: &V) -> usize {
+        self(key, value)
+    }
+}
+
+/// The weigher held by a cache. Without a custom weigher every entry weighs
+/// one, so the capacity is an entry count.
+pub(crate) struct EntryWeigher<K, V>(Option<Arc<dyn Weigher<K, V> + Send + Sync>>);
+
+impl<K, V> EntryWeigher<K, V> {
+    pub(crate) fn unit() -> Self {
+        Self(None)
+    }
+
+    pub(crate) fn new(weigher: impl Weigher<K, V> + Send + Sync + 'static) -> Self {
+        Self(Some(Arc::new(weigher)))
+    }
+
+    pub(crate) fn weigh(&self, key: &K, value: &V) -> usize {
+        match &self.0 {
+            Some(weigher) => weigher.weigh(key, value),
+            None => 1,
+        }
+    }
+}
+
+impl<K, V> Clone for EntryWeigher<K, V> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<K, V> fmt::Debug for EntryWeigher<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(if self.0.is_some() { "Custom" } else { "Unit" })
+    }
+}
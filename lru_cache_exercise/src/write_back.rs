@@ -0,0 +1,342 @@
+//! A write-back LRU buffer in front of a persistent store.
+
+mod store;
+
+pub use store::{BackingStore, FileStore, MemoryStore};
+
+use std::fmt;
+use std::hash::Hash;
+use std::io;
+
+use crate::policy::LruCache;
+use crate::stats::CacheStats;
+
+/// A cached value and whether it differs from the backing store's copy.
+#[derive(Debug, Clone)]
+struct Cached<V> {
+    value: V,
+    dirty: bool,
+}
+
+/// An LRU cache that buffers writes to a [`BackingStore`].
+///
+/// [`put`](Self::put) only marks the entry dirty. Dirty entries are written
+/// to the store when they are evicted from the least recently used end, or
+/// when [`flush`](Self::flush) or [`flush_key`](Self::flush_key) is called.
+/// Entries that were read from the store and never changed are dropped on
+/// eviction without writing. Misses read through from the store.
+///
+/// A failed write leaves the cache unchanged and the entry still dirty, so
+/// the operation can be retried. Dropping the cache flushes what it can and
+/// ignores errors; call `flush` first to see them.
+///
+/// ```
+/// use lru_cache_exercise::{MemoryStore, WriteBackCache};
+///
+/// let store = MemoryStore::new();
+/// let mut cache = WriteBackCache::new(2, store.clone());
+/// cache.put("a", 1).unwrap();
+/// cache.put("b", 2).unwrap();
+/// assert_eq!(store.writes(), 0);
+/// // Evicting "a" writes it back.
+/// cache.put("c", 3).unwrap();
+/// assert_eq!(store.get(&"a"), Some(1));
+/// cache.flush().unwrap();
+/// assert_eq!(store.len(), 3);
+/// ```
+pub struct WriteBackCache<K, V> {
+    cache: LruCache<K, Cached<V>>,
+    store: Box<dyn BackingStore<K, V> + Send>,
+}
+
+impl<K, V> WriteBackCache<K, V> {
+    /// Creates an empty cache that holds at most `capacity` entries in front
+    /// of `store`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero, since values read from the store could
+    /// not be handed out.
+    pub fn new(capacity: usize, store: impl BackingStore<K, V> + Send + 'static) -> Self {
+        assert!(capacity > 0, "a write-back cache needs a non-zero capacity");
+        Self {
+            cache: LruCache::new(capacity),
+            store: Box::new(store),
+        }
+    }
+
+    /// Returns the number of cached entries.
+    pub fn len(&self) -> usize {
+        self.cache.len()
+    }
+
+    /// Returns `true` if no entries are cached.
+    pub fn is_empty(&self) -> bool {
+        self.cache.is_empty()
+    }
+
+    /// Returns the maximum number of cached entries.
+    pub fn capacity(&self) -> usize {
+        self.cache.capacity()
+    }
+
+    /// Returns the number of cached entries not yet written to the store.
+    pub fn dirty_len(&self) -> usize {
+        self.cache.values().filter(|cached| cached.dirty).count()
+    }
+
+    /// Returns the hit, miss and eviction counts. Misses include reads that
+    /// were then found in the store.
+    pub fn stats(&self) -> CacheStats {
+        self.cache.stats()
+    }
+
+    /// Writes every dirty entry to the store and marks it clean. Stops at the
+    /// first error, leaving the remaining entries dirty.
+    pub fn flush(&mut self) -> io::Result<()> {
+        for (key, cached) in self.cache.iter_mut() {
+            if cached.dirty {
+                self.store.write(key, &cached.value)?;
+                cached.dirty = false;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<K: Hash + Eq + Clone, V> WriteBackCache<K, V> {
+    /// Returns the value for `key`, reading it from the store on a miss.
+    /// A value read from the store is cached clean, which may evict and
+    /// write back the least recently used entry.
+    pub fn get(&mut self, key: &K) -> io::Result<Option<&V>> {
+        Ok(self.get_mut_inner(key, false)?.map(|value| &*value))
+    }
+
+    /// Like [`get`](Self::get), but returns a mutable reference and marks the
+    /// entry dirty.
+    pub fn get_mut(&mut self, key: &K) -> io::Result<Option<&mut V>> {
+        self.get_mut_inner(key, true)
+    }
+
+    /// Caches `value` for `key` as a dirty entry, without writing it. If the
+    /// cache is full, the least recently used entry is written back first
+    /// (when dirty) and evicted.
+    pub fn put(&mut self, key: K, value: V) -> io::Result<()> {
+        if !self.cache.contains_key(&key) {
+            self.make_room()?;
+        }
+        self.cache.put(key, Cached { value, dirty: true });
+        Ok(())
+    }
+
+    /// Returns `true` if `key` is cached with changes not yet written.
+    pub fn is_dirty(&self, key: &K) -> bool {
+        self.cache.peek(key).is_some_and(|cached| cached.dirty)
+    }
+
+    /// Writes `key` to the store if it is cached and dirty, and marks it
+    /// clean. Returns whether anything was written.
+    pub fn flush_key(&mut self, key: &K) -> io::Result<bool> {
+        match self.cache.peek_mut(key) {
+            Some(cached) if cached.dirty => {
+                self.store.write(key, &cached.value)?;
+                cached.dirty = false;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// Deletes `key` from the store and drops it from the cache, dirty or
+    /// not. Returns the cached value, if any.
+    pub fn remove(&mut self, key: &K) -> io::Result<Option<V>> {
+        self.store.delete(key)?;
+        Ok(self.cache.remove(key).map(|cached| cached.value))
+    }
+
+    fn get_mut_inner(&mut self, key: &K, mark_dirty: bool) -> io::Result<Option<&mut V>> {
+        let index = match self.cache.lookup(key) {
+            Some(index) => index,
+            None => {
+                let Some(value) = self.store.read(key)? else {
+                    return Ok(None);
+                };
+                self.make_room()?;
+                self.cache.put(
+                    key.clone(),
+                    Cached {
+                        value,
+                        dirty: false,
+                    },
+                );
+                self.cache.mru_index().expect("a value was just inserted")
+            }
+        };
+        let cached = self.cache.value_at_mut(index);
+        cached.dirty |= mark_dirty;
+        Ok(Some(&mut cached.value))
+    }
+
+    /// Writes back and evicts least recently used entries until one more
+    /// fits, so that the cache never evicts a dirty entry on its own. Nothing
+    /// is evicted unless its write succeeded.
+    fn make_room(&mut self) -> io::Result<()> {
+        while self.cache.len() >= self.cache.capacity() {
+            let Some((key, cached)) = self.cache.peek_lru() else {
+                break;
+            };
+            if cached.dirty {
+                self.store.write(key, &cached.value)?;
+            }
+            self.cache.pop_lru();
+        }
+        Ok(())
+    }
+}
+
+impl<K, V> Drop for WriteBackCache<K, V> {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug> fmt::Debug for WriteBackCache<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map()
+            .entries(self.cache.iter().map(|(key, cached)| (key, &cached.value)))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A store whose writes can be made to fail.
+    struct FlakyStore {
+        inner: MemoryStore<u32, u32>,
+        fail: bool,
+    }
+
+    impl BackingStore<u32, u32> for FlakyStore {
+        fn read(&self, key: &u32) -> io::Result<Option<u32>> {
+            self.inner.read(key)
+        }
+
+        fn write(&mut self, key: &u32, value: &u32) -> io::Result<()> {
+            if self.fail {
+                return Err(io::Error::other("disk full"));
+            }
+            self.inner.write(key, value)
+        }
+
+        fn delete(&mut self, key: &u32) -> io::Result<()> {
+            self.inner.delete(key)
+        }
+    }
+
+    #[test]
+    fn test_put_does_not_write_through() {
+        let store = MemoryStore::new();
+        let mut cache = WriteBackCache::new(4, store.clone());
+        cache.put(1, 10).unwrap();
+        cache.put(1, 11).unwrap();
+        assert!(cache.is_dirty(&1));
+        assert_eq!(store.writes(), 0);
+        assert_eq!(cache.get(&1).unwrap(), Some(&11));
+    }
+
+    #[test]
+    fn test_dirty_tail_is_written_on_eviction() {
+        let store = MemoryStore::new();
+        let mut cache = WriteBackCache::new(2, store.clone());
+        cache.put(1, 10).unwrap();
+        cache.put(2, 20).unwrap();
+        cache.put(3, 30).unwrap();
+        assert_eq!(store.get(&1), Some(10));
+        assert_eq!(store.writes(), 1);
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn test_clean_eviction_does_not_write() {
+        let store = MemoryStore::new();
+        store.insert(1, 10);
+        store.insert(2, 20);
+        let mut cache = WriteBackCache::new(1, store.clone());
+        assert_eq!(cache.get(&1).unwrap(), Some(&10));
+        assert_eq!(cache.get(&2).unwrap(), Some(&20));
+        assert_eq!(cache.get(&1).unwrap(), Some(&10));
+        assert_eq!(store.writes(), 0);
+        assert_eq!(cache.get(&3).unwrap(), None);
+    }
+
+    #[test]
+    fn test_get_mut_marks_dirty() {
+        let store = MemoryStore::new();
+        store.insert(1, 10);
+        store.insert(2, 20);
+        let mut cache = WriteBackCache::new(1, store.clone());
+        *cache.get_mut(&1).unwrap().unwrap() += 1;
+        assert!(cache.is_dirty(&1));
+        cache.get(&2).unwrap();
+        assert_eq!(store.get(&1), Some(11));
+    }
+
+    #[test]
+    fn test_flush_and_flush_key() {
+        let store = MemoryStore::new();
+        let mut cache = WriteBackCache::new(4, store.clone());
+        cache.put(1, 10).unwrap();
+        cache.put(2, 20).unwrap();
+        assert!(cache.flush_key(&1).unwrap());
+        assert!(!cache.flush_key(&1).unwrap());
+        assert_eq!(cache.dirty_len(), 1);
+        cache.flush().unwrap();
+        assert_eq!(cache.dirty_len(), 0);
+        assert_eq!(store.writes(), 2);
+        cache.flush().unwrap();
+        assert_eq!(store.writes(), 2);
+    }
+
+    #[test]
+    fn test_failed_write_back_keeps_entry() {
+        let mut cache = WriteBackCache::new(
+            1,
+            FlakyStore {
+                inner: MemoryStore::new(),
+                fail: true,
+            },
+        );
+        cache.put(1, 10).unwrap();
+        assert!(cache.put(2, 20).is_err());
+        assert!(cache.is_dirty(&1));
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn test_remove_deletes_from_store() {
+        let store = MemoryStore::new();
+        store.insert(1, 10);
+        let mut cache = WriteBackCache::new(2, store.clone());
+        cache.get(&1).unwrap();
+        assert_eq!(cache.remove(&1).unwrap(), Some(10));
+        assert_eq!(store.get(&1), None);
+        assert_eq!(cache.get(&1).unwrap(), None);
+    }
+
+    #[test]
+    fn test_drop_flushes() {
+        let store = MemoryStore::new();
+        let mut cache = WriteBackCache::new(2, store.clone());
+        cache.put(1, 10).unwrap();
+        drop(cache);
+        assert_eq!(store.get(&1), Some(10));
+    }
+
+    #[test]
+    #[should_panic(expected = "non-zero capacity")]
+    fn test_zero_capacity_panics() {
+        WriteBackCache::new(0, MemoryStore::<u32, u32>::new());
+    }
+}
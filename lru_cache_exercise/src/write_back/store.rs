@@ -0,0 +1,299 @@
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::fs::{self, File};
+use std::hash::Hash;
+use std::io::{self, Write as _};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+
+/// Persistent storage behind a [`WriteBackCache`](super::WriteBackCache).
+pub trait BackingStore<K, V> {
+    /// Returns the stored value for `key`, or `None` if there is none.
+    fn read(&self, key: &K) -> io::Result<Option<V>>;
+
+    /// Stores `value` under `key`, replacing any previous value.
+    fn write(&mut self, key: &K, value: &V) -> io::Result<()>;
+
+    /// Deletes the value stored under `key`. Deleting a missing key is not
+    /// an error.
+    fn delete(&mut self, key: &K) -> io::Result<()>;
+}
+
+/// A [`BackingStore`] held in memory, mainly for tests.
+///
+/// Clones share the same contents, so a test can keep one clone to inspect
+/// what the cache wrote through another.
+#[derive(Debug)]
+pub struct MemoryStore<K, V> {
+    map: Arc<Mutex<HashMap<K, V>>>,
+    writes: Arc<AtomicUsize>,
+}
+
+impl<K, V> MemoryStore<K, V> {
+    /// Creates an empty store.
+    pub fn new() -> Self {
+        Self {
+            map: Arc::new(Mutex::new(HashMap::new())),
+            writes: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// Returns the number of stored values.
+    pub fn len(&self) -> usize {
+        self.map
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .len()
+    }
+
+    /// Returns `true` if nothing is stored.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns how many times [`BackingStore::write`] has been called.
+    pub fn writes(&self) -> usize {
+        self.writes.load(Ordering::SeqCst)
+    }
+}
+
+impl<K: Hash + Eq, V: Clone> MemoryStore<K, V> {
+    /// Returns a clone of the stored value for `key`.
+    pub fn get(&self, key: &K) -> Option<V> {
+        let map = self.map.lock().unwrap_or_else(PoisonError::into_inner);
+        map.get(key).cloned()
+    }
+
+    /// Stores `value` directly, without counting it as a write.
+    pub fn insert(&self, key: K, value: V) {
+        let mut map = self.map.lock().unwrap_or_else(PoisonError::into_inner);
+        map.insert(key, value);
+    }
+}
+
+impl<K, V> Clone for MemoryStore<K, V> {
+    fn clone(&self) -> Self {
+        Self {
+            map: Arc::clone(&self.map),
+            writes: Arc::clone(&self.writes),
+        }
+    }
+}
+
+impl<K, V> Default for MemoryStore<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Hash + Eq + Clone, V: Clone> BackingStore<K, V> for MemoryStore<K, V> {
+    fn read(&self, key: &K) -> io::Result<Option<V>> {
+        Ok(self.get(key))
+    }
+
+    fn write(&mut self, key: &K, value: &V) -> io::Result<()> {
+        self.writes.fetch_add(1, Ordering::SeqCst);
+        self.insert(key.clone(), value.clone());
+        Ok(())
+    }
+
+    fn delete(&mut self, key: &K) -> io::Result<()> {
+        let mut map = self.map.lock().unwrap_or_else(PoisonError::into_inner);
+        map.remove(key);
+        Ok(())
+    }
+}
+
+/// A [`BackingStore`] that keeps each value in its own file in a directory.
+///
+/// Keys are byte strings, named on disk by their hex encoding; values are
+/// raw bytes. Keys too long for one file name are split across nested
+/// directories, which are removed again once empty. Writes go to a temporary
+/// file that is synced to disk and then renamed over the old one, and the
+/// rename is synced in turn, so a crash never leaves a half-written value
+/// behind and a value whose write returned stays written.
+#[derive(Debug, Clone)]
+pub struct FileStore {
+    dir: PathBuf,
+}
+
+impl FileStore {
+    /// Opens a store in `dir`, creating the directory if needed.
+    pub fn open(dir: impl AsRef<Path>) -> io::Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+
+    /// Returns the directory holding the values.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The most key bytes encoded in one path component, which keeps each
+    /// name, with its prefix and a `.tmp` extension, well under the usual
+    /// 255-byte limit.
+    const BYTES_PER_COMPONENT: usize = 100;
+
+    /// Returns the file for `key`. Every chunk of the key but the last names
+    /// a directory, prefixed `d` so that it never clashes with a file, which
+    /// is prefixed `k`.
+    fn path(&self, key: &[u8]) -> PathBuf {
+        let mut path = self.dir.clone();
+        let mut chunks = key.chunks(Self::BYTES_PER_COMPONENT).peekable();
+        loop {
+            let chunk = chunks.next().unwrap_or_default();
+            let last = chunks.peek().is_none();
+            let mut name = String::with_capacity(chunk.len() * 2 + 1);
+            name.push(if last { 'k' } else { 'd' });
+            for byte in chunk {
+                let _ = write!(name, "{byte:02x}");
+            }
+            path.push(name);
+            if last {
+                return path;
+            }
+        }
+    }
+
+    /// Syncs the directories from `path`'s parent up to the store's own, so
+    /// that the entries naming `path` survive a crash. Directories cannot be
+    /// opened for syncing outside Unix, where this does nothing.
+    fn sync_parents(&self, path: &Path) -> io::Result<()> {
+        if cfg!(unix) {
+            for dir in path.ancestors().skip(1) {
+                File::open(dir)?.sync_all()?;
+                if dir == self.dir {
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes the directories between `path` and the store's directory
+    /// while they are empty.
+    fn remove_empty_parents(&self, path: &Path) {
+        for dir in path.ancestors().skip(1) {
+            if dir == self.dir || fs::remove_dir(dir).is_err() {
+                break;
+            }
+        }
+    }
+}
+
+impl<K: AsRef<[u8]>> BackingStore<K, Vec<u8>> for FileStore {
+    fn read(&self, key: &K) -> io::Result<Option<Vec<u8>>> {
+        match fs::read(self.path(key.as_ref())) {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(error),
+        }
+    }
+
+    fn write(&mut self, key: &K, value: &Vec<u8>) -> io::Result<()> {
+        let path = self.path(key.as_ref());
+        if let Some(parent) = path.parent().filter(|&parent| parent != self.dir) {
+            fs::create_dir_all(parent)?;
+        }
+        let tmp = path.with_extension("tmp");
+        let mut file = File::create(&tmp)?;
+        file.write_all(value)?;
+        file.sync_all()?;
+        fs::rename(&tmp, &path)?;
+        self.sync_parents(&path)
+    }
+
+    fn delete(&mut self, key: &K) -> io::Result<()> {
+        let path = self.path(key.as_ref());
+        match fs::remove_file(&path) {
+            Err(error) if error.kind() != io::ErrorKind::NotFound => Err(error),
+            _ => {
+                self.remove_empty_parents(&path);
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::WriteBackCache;
+
+    /// A fresh directory under the system temp dir, removed on drop.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir()
+                .join(format!("lru-cache-exercise-{name}-{}", std::process::id()));
+            let _ = fs::remove_dir_all(&path);
+            Self(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn test_file_store_round_trip() {
+        let dir = TempDir::new("round-trip");
+        let mut store = FileStore::open(&dir.0).unwrap();
+        assert_eq!(store.read(&"a/b").unwrap(), None);
+        store.write(&"a/b", &b"one".to_vec()).unwrap();
+        store.write(&"a/b", &b"two".to_vec()).unwrap();
+        assert_eq!(store.read(&"a/b").unwrap(), Some(b"two".to_vec()));
+        store.delete(&"a/b").unwrap();
+        store.delete(&"a/b").unwrap();
+        assert_eq!(store.read(&"a/b").unwrap(), None);
+    }
+
+    #[test]
+    fn test_file_store_long_keys() {
+        let dir = TempDir::new("long-keys");
+        let mut store = FileStore::open(&dir.0).unwrap();
+        let long = vec![b'x'; 300];
+        let prefix = long[..100].to_vec();
+        store.write(&long, &b"long".to_vec()).unwrap();
+        store.write(&prefix, &b"prefix".to_vec()).unwrap();
+        assert_eq!(store.read(&long).unwrap(), Some(b"long".to_vec()));
+        assert_eq!(store.read(&prefix).unwrap(), Some(b"prefix".to_vec()));
+
+        store.delete(&long).unwrap();
+        assert_eq!(store.read(&long).unwrap(), None);
+        assert_eq!(fs::read_dir(&dir.0).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_write_back_cache_over_files() {
+        let dir = TempDir::new("cache");
+        let store = FileStore::open(&dir.0).unwrap();
+        let mut cache = WriteBackCache::new(1, store.clone());
+        cache.put("x".to_string(), b"1".to_vec()).unwrap();
+        assert_eq!(
+            BackingStore::<String, _>::read(&store, &"x".to_string()).unwrap(),
+            None
+        );
+        cache.put("y".to_string(), b"2".to_vec()).unwrap();
+        assert_eq!(
+            BackingStore::<String, _>::read(&store, &"x".to_string()).unwrap(),
+            Some(b"1".to_vec())
+        );
+        drop(cache);
+
+        let mut reopened = WriteBackCache::new(4, FileStore::open(&dir.0).unwrap());
+        assert_eq!(
+            reopened.get(&"y".to_string()).unwrap(),
+            Some(&b"2".to_vec())
+        );
+        assert_eq!(
+            reopened.get(&"x".to_string()).unwrap(),
+            Some(&b"1".to_vec())
+        );
+    }
+}